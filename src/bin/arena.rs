@@ -0,0 +1,388 @@
+//! Play two bots against each other with the local rules engine
+//!
+//! Usage: `arena <bot-a> <bot-b> [--rounds N] [--seed N] [--map FILE] [--verbose]`
+//!
+//! Each bot is a command line, split on whitespace and spawned with piped
+//! stdin and stdout. The bots are sent the same settings, updates and
+//! actions as the competition engine, and their replies drive the
+//! simulator. Snippets, bombs and bugs are scattered with a seeded random
+//! number generator, so the same seed plays out the same game for
+//! deterministic bots.
+extern crate rand;
+extern crate ruby_the_ms_hack_man_bot as hackman;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use hackman::bot::Settings;
+use hackman::distance::DistanceTable;
+use hackman::field::{Cell, CellItem, Field, GateDirection, Point};
+use hackman::message::{Action, Message, Setting, Update};
+use hackman::player::{CharacterType, Move};
+use hackman::simulator::{GameState, ME, OPPONENT};
+
+/// The default map, one character per cell:
+/// `x` wall, `.` empty, `L`/`R` gates, `S` bug spawn, `0`/`1` players.
+const DEFAULT_MAP: &str = "\
+........x.x........
+.xxx.x..x.x..x.xxx.
+.x...xx.....xx...x.
+.x.x....xxx....x.x.
+...x.xx.....xx.x...
+xx...x..xSx..x...xx
+...x.x.xx.xx.x.x...
+L..x...0...1...x..R
+...x.x.xx.xx.x.x...
+xx...x.......x...xx
+...x.xx.x.x.xx.x...
+.x.x....x.x....x.x.
+.x...xx.....xx...x.
+.xxx.x..xSx..x.xxx.
+.........x.........";
+
+const TIME_BANK: i32 = 10000;
+const TIME_PER_MOVE: i32 = 500;
+const DEFAULT_ROUNDS: i32 = 250;
+
+/// Snippets are topped up to this many at the start of every round.
+const SNIPPETS_ON_FIELD: usize = 4;
+/// A bomb is dropped on the field every this many rounds.
+const BOMB_INTERVAL: i32 = 8;
+/// A spawn point starts counting down every this many rounds.
+const BUG_INTERVAL: i32 = 10;
+const BUG_COUNTDOWN: i32 = 3;
+
+struct Options {
+    bots: Vec<String>,
+    rounds: i32,
+    seed: usize,
+    map: Option<String>,
+    verbose: bool,
+}
+
+/// A bot process and the lines it has written.
+struct BotProcess {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    time_bank: i32,
+}
+
+impl BotProcess {
+    fn spawn(name: String, command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = child.stdout.take().expect("piped stdout");
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(BotProcess { name, child, stdin, lines, time_bank: TIME_BANK })
+    }
+
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        writeln!(self.stdin, "{}", message)?;
+        self.stdin.flush()
+    }
+
+    /// Send an action and wait for the first reply `accept` understands.
+    ///
+    /// Other output is skipped, and so is anything the bot wrote before the
+    /// action, like a reply that came too late for the last one. The wait
+    /// counts against the time bank, and a bot that runs out of time gets
+    /// `None`.
+    fn ask<T, F>(&mut self, action: fn(i32) -> Action, accept: F) -> Option<T>
+        where F: Fn(&str) -> Option<T>
+    {
+        while let Ok(line) = self.lines.try_recv() {
+            eprintln!("{}: ignored output {:?}", self.name, line);
+        }
+        let message = Message::Action(action(self.time_bank));
+        if self.send(&message).is_err() {
+            return None;
+        }
+
+        let start = Instant::now();
+        let mut reply = None;
+        while reply.is_none() {
+            let spent = elapsed_ms(start);
+            if spent >= self.time_bank {
+                break;
+            }
+            let wait = Duration::from_millis((self.time_bank - spent) as u64);
+            match self.lines.recv_timeout(wait) {
+                Ok(line) => {
+                    reply = accept(&line);
+                    if reply.is_none() {
+                        eprintln!("{}: ignored output {:?}", self.name, line);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        self.time_bank = (self.time_bank - elapsed_ms(start) + TIME_PER_MOVE).min(TIME_BANK);
+        if reply.is_none() {
+            eprintln!("{}: no reply to \"{}\"", self.name, message);
+            self.time_bank = self.time_bank.max(TIME_PER_MOVE);
+        }
+        reply
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn elapsed_ms(start: Instant) -> i32 {
+    let elapsed = start.elapsed();
+    (elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())) as i32
+}
+
+fn main() {
+    let status = match run() {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    };
+    ::std::process::exit(status);
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(env::args().skip(1))?;
+    let map = match options.map {
+        Some(ref path) => {
+            let mut text = String::new();
+            File::open(path)
+                .and_then(|mut f| f.read_to_string(&mut text))
+                .map_err(|e| format!("Could not read map {}: {}", path, e))?;
+            text
+        }
+        None => DEFAULT_MAP.to_string(),
+    };
+    let (width, height, cells) = parse_map(&map)?;
+
+    let mut field = Field::new(width, height);
+    field.update_field(cells).map_err(|e| e.to_string())?;
+    let distances = DistanceTable::new(&field);
+    let settings = Settings { max_rounds: options.rounds, ..Settings::default() };
+    let mut state = GameState::new(&field, &HashMap::new(), &settings);
+    let mut rng: StdRng = SeedableRng::from_seed(&[options.seed][..]);
+
+    let names = ["player0", "player1"];
+    let mut bots = Vec::new();
+    for (name, command) in names.iter().zip(&options.bots) {
+        let bot = BotProcess::spawn(name.to_string(), command)
+            .map_err(|e| format!("Could not start {}: {}", command, e))?;
+        bots.push(bot);
+    }
+
+    for (id, bot) in bots.iter_mut().enumerate() {
+        let settings = vec![Setting::TimeBank(TIME_BANK),
+                            Setting::TimePerMove(TIME_PER_MOVE),
+                            Setting::PlayerNames(names.iter().map(|n| n.to_string()).collect()),
+                            Setting::YourBot(names[id].to_string()),
+                            Setting::YourBotId(id as i32),
+                            Setting::FieldWidth(width as i32),
+                            Setting::FieldHeight(height as i32),
+                            Setting::MaxRounds(options.rounds)];
+        for setting in settings {
+            bot.send(&Message::Settings(setting)).map_err(|e| e.to_string())?;
+        }
+        let character = bot.ask(|time_to_respond| Action::Character { time_to_respond }, |reply| {
+            reply.trim().parse::<CharacterType>().ok().map(|c| c.to_string())
+        });
+        eprintln!("{} plays {}", bot.name, character.unwrap_or_else(|| CharacterType::default().to_string()));
+    }
+
+    while !state.is_over() {
+        scatter(&mut state, &field, &mut rng);
+        let round = state.round + 1;
+        let cells = state.to_cells(&field);
+
+        let mut moves = Vec::new();
+        for bot in bots.iter_mut() {
+            let mut updates = vec![Update::GameRound(round), Update::GameField(cells.clone())];
+            for (name, player) in names.iter().zip(state.players.iter()) {
+                updates.push(Update::PlayerSnippets(name.to_string(), player.snippets));
+                updates.push(Update::PlayerBombs(name.to_string(), player.bombs));
+            }
+            for update in updates {
+                bot.send(&Message::Update(update)).map_err(|e| e.to_string())?;
+            }
+            let reply = bot.ask(|time_to_respond| Action::Move { time_to_respond },
+                                |reply| reply.parse::<Move>().ok());
+            moves.push(reply.unwrap_or_else(Move::new));
+        }
+
+        if options.verbose {
+            eprintln!("round {}: {} | {}", round, moves[ME], moves[OPPONENT]);
+        }
+        state.step(&field, &distances, [&moves[ME], &moves[OPPONENT]]);
+    }
+
+    report(&state, &names);
+    Ok(())
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let usage = "Usage: arena <bot-a> <bot-b> [--rounds N] [--seed N] [--map FILE] [--verbose]";
+    let mut options = Options {
+        bots: Vec::new(),
+        rounds: DEFAULT_ROUNDS,
+        seed: 0,
+        map: None,
+        verbose: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rounds" => {
+                options.rounds = args.next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| usage.to_string())?
+            }
+            "--seed" => {
+                options.seed = args.next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| usage.to_string())?
+            }
+            "--map" => options.map = Some(args.next().ok_or_else(|| usage.to_string())?),
+            "--verbose" => options.verbose = true,
+            _ => options.bots.push(arg),
+        }
+    }
+
+    if options.bots.len() != 2 {
+        return Err(usage.to_string());
+    }
+    Ok(options)
+}
+
+/// Read a map in the format of `DEFAULT_MAP`.
+fn parse_map(text: &str) -> Result<(usize, usize, Vec<Cell>), String> {
+    let rows: Vec<&str> = text.lines().map(str::trim).filter(|r| !r.is_empty()).collect();
+    let width = rows.first().map_or(0, |r| r.len());
+    let mut cells = Vec::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        if row.len() != width {
+            return Err(format!("Map row {} is {} cells wide, expected {}", y, row.len(), width));
+        }
+        for c in row.chars() {
+            let item = match c {
+                '.' => CellItem::Empty,
+                'x' => CellItem::Inaccessible,
+                'L' => CellItem::Gate(GateDirection::Left),
+                'R' => CellItem::Gate(GateDirection::Right),
+                'S' => CellItem::Spawn(None),
+                '0' => CellItem::Player(0),
+                '1' => CellItem::Player(1),
+                _ => return Err(format!("Unknown map cell {:?} in row {}", c, y)),
+            };
+            cells.push(Some(item).into_iter().collect());
+        }
+    }
+    Ok((width, rows.len(), cells))
+}
+
+/// Drop in the snippets, bombs and bugs that the engine adds during a game.
+fn scatter(state: &mut GameState, field: &Field, rng: &mut StdRng) {
+    while state.snippets.len() < SNIPPETS_ON_FIELD {
+        match free_cell(state, field, rng) {
+            Some(p) => state.snippets.push(p),
+            None => break,
+        }
+    }
+    if state.round > 0 && state.round % BOMB_INTERVAL == 0 {
+        if let Some(p) = free_cell(state, field, rng) {
+            state.bombs.push(p);
+        }
+    }
+    if state.round > 0 && state.round % BUG_INTERVAL == 0 {
+        let idle: Vec<usize> = (0..state.spawns.len())
+            .filter(|&i| state.spawns[i].countdown.is_none())
+            .collect();
+        if !idle.is_empty() {
+            let i = idle[rng.gen_range(0, idle.len())];
+            state.spawns[i].countdown = Some(BUG_COUNTDOWN);
+        }
+    }
+}
+
+/// Pick a random walkable cell with nothing in it.
+fn free_cell(state: &GameState, field: &Field, rng: &mut StdRng) -> Option<Point> {
+    let taken = |p: &Point| {
+        state.players.iter().any(|player| player.point == Some(*p)) ||
+        state.enemies.iter().any(|e| e.point == *p) ||
+        state.spawns.iter().any(|s| s.point == *p) ||
+        state.snippets.contains(p) ||
+        state.bombs.contains(p) ||
+        state.ticking_bombs.iter().any(|b| b.point == *p)
+    };
+    let free: Vec<Point> = (0..field.height())
+        .flat_map(|y| (0..field.width()).map(move |x| Point::new(x, y)))
+        .filter(|&p| field.is_walkable(p))
+        .filter(|&p| field.cell(p).and_then(Cell::gate).is_none())
+        .filter(|p| !taken(p))
+        .collect();
+    if free.is_empty() {
+        None
+    } else {
+        Some(free[rng.gen_range(0, free.len())])
+    }
+}
+
+fn report(state: &GameState, names: &[&str]) {
+    for (name, player) in names.iter().zip(state.players.iter()) {
+        println!("{}: {} snippets{}",
+                 name,
+                 player.snippets,
+                 if player.alive { "" } else { ", dead" });
+    }
+    let (me, them) = (&state.players[ME], &state.players[OPPONENT]);
+    let winner = match (me.alive, them.alive) {
+        (true, false) => Some(names[ME]),
+        (false, true) => Some(names[OPPONENT]),
+        _ if me.snippets > them.snippets => Some(names[ME]),
+        _ if me.snippets < them.snippets => Some(names[OPPONENT]),
+        _ => None,
+    };
+    match winner {
+        Some(name) => println!("{} wins after {} rounds", name, state.round),
+        None => println!("Draw after {} rounds", state.round),
+    }
+}
+
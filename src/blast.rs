@@ -0,0 +1,171 @@
+//! Bomb explosions and when they happen
+use field::{Field, Point, TickingBomb};
+use player::MoveType;
+use simple_matrix::Matrix;
+
+/// Get the cells caught in the explosion of a bomb at `origin`.
+///
+/// The blast covers the bomb's own cell and travels along its row and
+/// column until it reaches a wall or the edge of the field.
+pub fn blast_cells(field: &Field, origin: Point) -> Vec<Point> {
+    let mut cells = vec![origin];
+    for &direction in &MoveType::DIRECTIONS {
+        let mut current = origin;
+        while let Some(next) = straight_step(field, current, direction) {
+            cells.push(next);
+            current = next;
+        }
+    }
+    cells
+}
+
+/// Step one cell in a direction without going through gates.
+fn straight_step(field: &Field, point: Point, direction: MoveType) -> Option<Point> {
+    let Point { x, y } = point;
+    let next = match direction {
+        MoveType::Up if y > 0 => Point::new(x, y - 1),
+        MoveType::Down => Point::new(x, y + 1),
+        MoveType::Left if x > 0 => Point::new(x - 1, y),
+        MoveType::Right => Point::new(x + 1, y),
+        _ => return None,
+    };
+    if field.is_walkable(next) {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+/// The cells hit by explosions on each of the next rounds.
+///
+/// Round 1 is the round following the current field update. A bomb caught
+/// in another bomb's blast goes off in the same round.
+#[derive(Clone, Debug)]
+pub struct BlastTimeline {
+    horizon: usize,
+    first_hit: Matrix<Option<usize>>,
+    rounds: Vec<Vec<Point>>,
+    detonations: Vec<(TickingBomb, usize)>,
+}
+
+impl BlastTimeline {
+    /// Work out the explosions of the field's ticking bombs up to `horizon` rounds ahead.
+    pub fn from_field(field: &Field, horizon: usize) -> Self {
+        BlastTimeline::new(field, &field.ticking_bombs, horizon)
+    }
+
+    /// Work out the explosions of the given bombs up to `horizon` rounds ahead.
+    pub fn new(field: &Field, bombs: &[TickingBomb], horizon: usize) -> Self {
+        let blasts: Vec<Vec<Point>> = bombs.iter()
+            .map(|bomb| blast_cells(field, bomb.point))
+            .collect();
+        let mut rounds: Vec<usize> = bombs.iter()
+            .map(|bomb| bomb.ticks.max(1) as usize)
+            .collect();
+
+        // Chain reactions pull detonations forward until nothing changes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..bombs.len() {
+                for j in 0..bombs.len() {
+                    if rounds[j] > rounds[i] && blasts[i].contains(&bombs[j].point) {
+                        rounds[j] = rounds[i];
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut timeline = BlastTimeline {
+            horizon,
+            first_hit: Matrix::filled(field.width(), field.height(), None),
+            rounds: vec![Vec::new(); horizon],
+            detonations: Vec::new(),
+        };
+        for ((bomb, blast), round) in bombs.iter().zip(blasts).zip(rounds) {
+            timeline.detonations.push((*bomb, round));
+            if round > horizon {
+                continue;
+            }
+            for point in blast {
+                let hit = &mut timeline.first_hit[(point.x, point.y)];
+                if hit.map_or(true, |r| round < r) {
+                    *hit = Some(round);
+                }
+                let cells = &mut timeline.rounds[round - 1];
+                if !cells.contains(&point) {
+                    cells.push(point);
+                }
+            }
+        }
+        timeline
+    }
+
+    /// Get how many rounds ahead the timeline looks.
+    pub fn horizon(&self) -> usize {
+        self.horizon
+    }
+
+    /// Get the first round in which the point is caught in an explosion.
+    pub fn dies_at(&self, point: Point) -> Option<usize> {
+        self.first_hit.get(point.x, point.y).and_then(|r| *r)
+    }
+
+    /// Check whether the point is caught in an explosion in the given round.
+    pub fn is_hit(&self, point: Point, round: usize) -> bool {
+        self.hits_on(round).contains(&point)
+    }
+
+    /// Get the cells caught in explosions in the given round.
+    pub fn hits_on(&self, round: usize) -> &[Point] {
+        match round {
+            0 => &[],
+            r if r > self.horizon => &[],
+            r => &self.rounds[r - 1],
+        }
+    }
+
+    /// Get every bomb with the round it goes off, chain reactions included.
+    pub fn detonations(&self) -> &[(TickingBomb, usize)] {
+        &self.detonations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blast::{blast_cells, BlastTimeline};
+    use field::{field_from, Point};
+
+    #[test]
+    fn blast_stops_at_walls_test() {
+        let field = field_from(4, 3, "\
+            .,.,x,.,\
+            .,.,.,.,\
+            .,x,.,.");
+        let mut cells = blast_cells(&field, Point::new(1, 0));
+        cells.sort_by_key(|p| (p.y, p.x));
+
+        assert_eq!(cells, vec![Point::new(0, 0), Point::new(1, 0), Point::new(1, 1)]);
+    }
+
+    #[test]
+    fn chain_reaction_test() {
+        let field = field_from(5, 3, "\
+            B1,.,.,B4,.,\
+            x,x,x,.,x,\
+            .,.,.,B3,.");
+        let timeline = BlastTimeline::from_field(&field, 4);
+
+        assert_eq!(timeline.dies_at(Point::new(4, 0)), Some(1));
+        assert_eq!(timeline.dies_at(Point::new(3, 1)), Some(1));
+        assert_eq!(timeline.dies_at(Point::new(0, 2)), Some(1));
+        assert!(timeline.is_hit(Point::new(3, 2), 1));
+        assert!(!timeline.is_hit(Point::new(3, 2), 3));
+        assert!(timeline.hits_on(4).is_empty());
+        assert!(timeline.detonations().iter().all(|&(_, round)| round == 1));
+
+        let later = BlastTimeline::from_field(&field_from(3, 1, ".,B3,."), 2);
+        assert_eq!(later.dies_at(Point::new(0, 0)), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Decide when to drop a bomb and with which fuse
+//!
+//! A bomb goes down on the cell we leave this round. Before any reason to
+//! drop it counts, every fuse is checked for a way out: after our chosen
+//! move we must be able to keep walking, or wait, without standing in any
+//! blast until ours has gone off. Of the fuses we can get away from, the
+//! reasons are tried in order:
+//!
+//! * `Trap` the opponent when it is in the blast and cannot get out of it.
+//! * Blow up a `Bug` that is following us down the blast's row or column.
+//! * `Deny` the opponent a snippet it is closer to, timing the blast for
+//!   when it gets there.
+use blast::{blast_cells, BlastTimeline};
+use error::{Error, Result};
+use field::{Field, Point, TickingBomb};
+use player::{check_fuse, MoveType, MAX_BOMB_TICKS, MIN_BOMB_TICKS};
+use strategy::GameView;
+
+/// Why a bomb is worth dropping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BombReason {
+    Trap,
+    Bug,
+    Deny,
+}
+
+/// A bomb to drop this round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BombPlan {
+    pub ticks: i32,
+    pub reason: BombReason,
+}
+
+/// Decide whether to drop a bomb on our cell before making `move_type`.
+pub fn plan_bomb(view: &GameView, move_type: MoveType) -> Option<BombPlan> {
+    let field = view.field;
+    if view.me().map_or(true, |me| me.bombs <= 0) {
+        return None;
+    }
+    let origin = field.player_position?;
+    let first_step = field.step(origin, move_type).unwrap_or(origin);
+    let blast = blast_cells(field, origin);
+
+    // Every fuse we could still walk away from
+    let timelines: Vec<(i32, BlastTimeline)> = (MIN_BOMB_TICKS..MAX_BOMB_TICKS + 1)
+        .map(|ticks| (ticks, timeline_with(field, origin, ticks)))
+        .filter(|&(ticks, ref timeline)| survives(field, timeline, vec![first_step], 1, ticks as usize))
+        .collect();
+
+    if let Some(opponent) = field.opponent_position {
+        if blast.contains(&opponent) {
+            let trap = timelines.iter()
+                .find(|&&(ticks, ref timeline)| !survives(field, timeline, vec![opponent], 0, ticks as usize));
+            if let Some(&(ticks, _)) = trap {
+                return Some(BombPlan { ticks, reason: BombReason::Trap });
+            }
+        }
+    }
+
+    // A bug following us walks over the bomb about as many rounds from now
+    // as it is steps behind us
+    let fits = |ticks: i32| timelines.iter().any(|&(t, _)| t == ticks);
+    let bug = field.enemies
+        .iter()
+        .filter(|enemy| blast.contains(&enemy.point))
+        .filter_map(|enemy| view.distances.distance(enemy.point, origin))
+        .map(|d| d as i32)
+        .filter(|&d| fits(d))
+        .min();
+    if let Some(ticks) = bug {
+        return Some(BombPlan { ticks, reason: BombReason::Bug });
+    }
+
+    let opponent = field.opponent_position?;
+    let contested = view.distances.nearest(opponent, &field.snippet_positions)?;
+    let (snippet, theirs) = contested;
+    let ours = view.distances.distance(origin, snippet).unwrap_or(::std::usize::MAX);
+    let ticks = theirs as i32;
+    if blast.contains(&snippet) && theirs < ours && fits(ticks) {
+        return Some(BombPlan { ticks, reason: BombReason::Deny });
+    }
+    None
+}
+
+/// Check that a bomb with `ticks` dropped on our cell before making
+/// `move_type` has a valid fuse and leaves us a way out of its blast.
+pub fn check_escape(view: &GameView, move_type: MoveType, ticks: i32) -> Result<()> {
+    check_fuse(ticks)?;
+    let field = view.field;
+    let escapes = match field.player_position {
+        Some(origin) => {
+            let first_step = field.step(origin, move_type).unwrap_or(origin);
+            let timeline = timeline_with(field, origin, ticks);
+            survives(field, &timeline, vec![first_step], 1, ticks as usize)
+        }
+        None => false,
+    };
+    if escapes {
+        Ok(())
+    } else {
+        Err(Error::InvalidBomb(format!("no way out of a blast with fuse {}", ticks)))
+    }
+}
+
+/// Get the explosions on the field once a bomb with `ticks` is added at `origin`.
+fn timeline_with(field: &Field, origin: Point, ticks: i32) -> BlastTimeline {
+    let mut bombs = field.ticking_bombs.clone();
+    bombs.push(TickingBomb { point: origin, ticks });
+    BlastTimeline::new(field, &bombs, ticks as usize)
+}
+
+/// Check whether someone standing on one of `starts` after round `from`
+/// can stay out of every blast up to and including round `to`.
+fn survives(field: &Field, timeline: &BlastTimeline, starts: Vec<Point>, from: usize, to: usize) -> bool {
+    let mut reachable: Vec<Point> = starts.into_iter()
+        .filter(|&p| !timeline.is_hit(p, from))
+        .collect();
+    for round in from + 1..to + 1 {
+        let mut next = Vec::new();
+        for &point in &reachable {
+            for p in field.neighbors(point).into_iter().chain(Some(point)) {
+                if !timeline.is_hit(p, round) && !next.contains(&p) {
+                    next.push(p);
+                }
+            }
+        }
+        reachable = next;
+    }
+    !reachable.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use bomb::{check_escape, plan_bomb, BombPlan, BombReason};
+    use player::{MoveType, Player};
+    use strategy::TestGame;
+
+    fn plan(width: usize, height: usize, cells: &str, bombs: i32, move_type: MoveType) -> Option<BombPlan> {
+        let mut game = TestGame::new(width, height, cells);
+        game.settings.name = "me".into();
+        let mut me = Player::new("me".into());
+        me.bombs = bombs;
+        game.players.insert(me.name.clone(), me);
+        plan_bomb(&game.view(), move_type)
+    }
+
+    #[test]
+    fn trap_test() {
+        // The opponent is stuck at the end of our corridor, and we can step
+        // round the corner below
+        let cells = "\
+            x,x,x,x,x,\
+            x,P1,.,P0,x,\
+            x,x,x,.,.";
+        assert_eq!(plan(5, 3, cells, 1, MoveType::Down),
+                   Some(BombPlan { ticks: 2, reason: BombReason::Trap }));
+        // Not without a bomb
+        assert_eq!(plan(5, 3, cells, 0, MoveType::Down), None);
+        // Not when we would walk into the blast
+        assert_eq!(plan(5, 3, cells, 1, MoveType::Left), None);
+
+        // The same goes for a bomb the strategy asks for itself
+        let game = TestGame::new(5, 3, cells);
+        assert!(check_escape(&game.view(), MoveType::Down, 2).is_ok());
+        assert!(check_escape(&game.view(), MoveType::Left, 2).is_err());
+        assert!(check_escape(&game.view(), MoveType::Down, 9).is_err());
+    }
+
+    #[test]
+    fn no_escape_test() {
+        // A dead end leaves nowhere to hide from our own bomb
+        let cells = "P0,.,E0,.,P1";
+        assert_eq!(plan(5, 1, cells, 1, MoveType::Left), None);
+    }
+
+    #[test]
+    fn bug_test() {
+        // A bug three steps behind us in the row we are leaving
+        let cells = "\
+            x,x,x,x,x,x,\
+            E0,.,.,P0,.,.,\
+            x,x,x,.,.,P1";
+        assert_eq!(plan(6, 3, cells, 1, MoveType::Down),
+                   Some(BombPlan { ticks: 3, reason: BombReason::Bug }));
+    }
+}
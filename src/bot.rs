@@ -2,17 +2,35 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use distance::DistanceTable;
+use error::Error;
+use eval::Weights;
 use field::Field;
-use player::Player;
+use player::{CharacterType, Player};
+use strategy::{self, Strategy};
 
-#[derive(Debug)]
 pub struct BotState {
     pub settings: Rc<RefCell<Settings>>,
     pub players: Rc<RefCell<HashMap<String, Player>>>,
     pub field: Rc<RefCell<Field>>,
+    /// All-pairs distances, built from the first field update of the game.
+    pub distances: Rc<RefCell<Option<DistanceTable>>>,
+    pub strategy: Rc<RefCell<Box<dyn Strategy>>>,
+    /// The latest value of every setting or update we don't understand,
+    /// keyed by its kind and key. Unknown cell codes are kept as kind
+    /// `cell` with the index of the last cell they were in.
+    pub unknown: Rc<RefCell<HashMap<(String, String), String>>>,
+    /// Problems that didn't stop a reply going out, for the message loop
+    /// to report.
+    pub errors: Rc<RefCell<Vec<Error>>>,
+    /// Fail on unknown settings, updates and cell codes instead of keeping them.
+    pub strict: bool,
+    /// Let every move take as long as the strategy wants, for replays where
+    /// the strategy's work is fixed instead.
+    pub replaying: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Settings {
     pub name: String,
     pub id: i32,
@@ -21,22 +39,32 @@ pub struct Settings {
     pub round: i32,
     pub max_rounds: i32,
     pub opponent_name: String,
+    pub character: CharacterType,
 }
 
 impl BotState {
     pub fn new() -> Self {
+        let default = strategy::STRATEGY_NAMES[0];
+        BotState::with_strategy(strategy::by_name(default, 0, &Weights::builtin()).expect("default strategy"))
+    }
+
+    pub fn with_strategy(strategy: Box<dyn Strategy>) -> Self {
         BotState {
-            settings: Rc::new(RefCell::new(Settings {
-                name: String::new(),
-                id: 0,
-                time_bank: 0,
-                time_per_move: 0,
-                round: 0,
-                max_rounds: 0,
-                opponent_name: String::new(),
-            })),
+            settings: Rc::new(RefCell::new(Settings::default())),
             players: Rc::new(RefCell::new(HashMap::new())),
             field: Rc::new(RefCell::new(Field::new(1, 1))),
+            distances: Rc::new(RefCell::new(None)),
+            strategy: Rc::new(RefCell::new(strategy)),
+            unknown: Rc::new(RefCell::new(HashMap::new())),
+            errors: Rc::new(RefCell::new(Vec::new())),
+            strict: false,
+            replaying: false,
         }
     }
 }
+
+impl Default for BotState {
+    fn default() -> Self {
+        BotState::new()
+    }
+}
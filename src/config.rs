@@ -0,0 +1,224 @@
+//! Command line and environment configuration for the bot
+//!
+//! Every option can be given as `--name value`, `--name=value`, or through
+//! its environment variable. Command line options win over the environment,
+//! and with neither the bot runs the way the competition expects.
+use std::str::FromStr;
+
+use error::*;
+use player::CharacterType;
+use strategy::STRATEGY_NAMES;
+
+pub const USAGE: &str = "\
+Usage: ruby_the_ms_hack_man_bot [options]
+
+Options:
+    --strategy NAME     move strategy (HACKMAN_STRATEGY)
+    --character NAME    bixie or bixiette (HACKMAN_CHARACTER)
+    --log-level LEVEL   quiet, error, info or debug (HACKMAN_LOG)
+    --seed N            random number seed (HACKMAN_SEED)
+    --weights PATH      read evaluation weights from a file (HACKMAN_WEIGHTS)
+    --record PATH       write a transcript of the game (HACKMAN_RECORD)
+    --replay PATH       play a transcript back with the options in its header, at a
+                        fixed amount of work per move (HACKMAN_REPLAY)
+    --socket PATH       talk over a Unix socket at PATH instead of stdio (HACKMAN_SOCKET)
+    --help              print this message";
+
+/// How much the bot writes to stderr.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Quiet,
+    Error,
+    Info,
+    Debug,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Config {
+    pub strategy: String,
+    pub character: CharacterType,
+    pub log_level: LogLevel,
+    pub seed: Option<usize>,
+    pub weights: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub socket: Option<String>,
+    pub help: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            strategy: STRATEGY_NAMES[0].into(),
+            character: CharacterType::Bixie,
+            log_level: LogLevel::Error,
+            seed: None,
+            weights: None,
+            record: None,
+            replay: None,
+            socket: None,
+            help: false,
+        }
+    }
+}
+
+/// The options that take a value, with their environment variables.
+const OPTIONS: [(&str, &str); 8] = [("strategy", "HACKMAN_STRATEGY"),
+                                    ("character", "HACKMAN_CHARACTER"),
+                                    ("log-level", "HACKMAN_LOG"),
+                                    ("seed", "HACKMAN_SEED"),
+                                    ("weights", "HACKMAN_WEIGHTS"),
+                                    ("record", "HACKMAN_RECORD"),
+                                    ("replay", "HACKMAN_REPLAY"),
+                                    ("socket", "HACKMAN_SOCKET")];
+
+impl Config {
+    /// Read the configuration from command line arguments, without the
+    /// program name, falling back to `env` for anything not given.
+    pub fn from_args<I, F>(args: I, env: F) -> Result<Config>
+        where I: IntoIterator<Item = String>,
+              F: Fn(&str) -> Option<String>
+    {
+        let mut config = Config::default();
+        let mut given: Vec<(String, String)> = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                config.help = true;
+                continue;
+            }
+            if !arg.starts_with("--") {
+                return Err(Error::InvalidArgument(arg.clone()));
+            }
+            let option = &arg[2..];
+            let (name, value) = match option.find('=') {
+                Some(i) => (option[..i].to_string(), option[i + 1..].to_string()),
+                None => {
+                    let value = args.next()
+                        .ok_or_else(|| Error::InvalidArgument(arg.clone()))?;
+                    (option.to_string(), value)
+                }
+            };
+            if !OPTIONS.iter().any(|&(known, _)| known == name) {
+                return Err(Error::InvalidArgument(arg.clone()));
+            }
+            given.push((name, value));
+        }
+
+        for &(name, var) in OPTIONS.iter() {
+            let value = given.iter()
+                .rev()
+                .find(|&(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .or_else(|| env(var));
+            if let Some(value) = value {
+                config.set(name, value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Get the options that decide how the bot plays, with the seed it plays
+    /// with, as arguments for `from_args` to read back.
+    pub fn play_args(&self, seed: usize) -> String {
+        let mut args = format!("--strategy {} --character {} --seed {}", self.strategy, self.character, seed);
+        if let Some(ref path) = self.weights {
+            args.push_str(&format!(" --weights {}", path));
+        }
+        args
+    }
+
+    fn set(&mut self, name: &str, value: String) -> Result<()> {
+        let invalid = || Error::InvalidArgument(format!("--{}={}", name, value));
+        match name {
+            "strategy" => {
+                if !STRATEGY_NAMES.contains(&value.as_str()) {
+                    return Err(Error::UnknownStrategy(value));
+                }
+                self.strategy = value;
+            }
+            "character" => self.character = value.parse().map_err(|_| invalid())?,
+            "log-level" => self.log_level = value.parse().map_err(|_| invalid())?,
+            "seed" => self.seed = Some(value.parse().map_err(|_| invalid())?),
+            "weights" => self.weights = Some(value),
+            "record" => self.record = Some(value),
+            "replay" => self.replay = Some(value),
+            "socket" => self.socket = Some(value),
+            _ => return Err(invalid()),
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "quiet" => Ok(LogLevel::Quiet),
+            "error" => Ok(LogLevel::Error),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(Error::InvalidArgument(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use config::{Config, LogLevel};
+    use player::CharacterType;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn defaults_test() {
+        let config = Config::from_args(vec![], |_| None).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn args_and_env_test() {
+        let env = |var: &str| match var {
+            "HACKMAN_CHARACTER" => Some("bixiette".to_string()),
+            "HACKMAN_SEED" => Some("7".to_string()),
+            "HACKMAN_STRATEGY" => Some("idle".to_string()),
+            _ => None,
+        };
+        let config = Config::from_args(args("--strategy wander --log-level=debug --seed 3"), env)
+            .unwrap();
+
+        assert_eq!(config.strategy, "wander");
+        assert_eq!(config.character, CharacterType::Bixiette);
+        assert_eq!(config.log_level, LogLevel::Debug);
+        assert_eq!(config.seed, Some(3));
+        assert_eq!(config.weights, None);
+        assert_eq!(config.record, None);
+    }
+
+    #[test]
+    fn play_args_test() {
+        let config = Config::from_args(args("--strategy mcts --character bixiette --weights w.txt --log-level quiet"),
+                                       |_| None)
+            .unwrap();
+        let played = Config::from_args(args(&config.play_args(9)), |_| None).unwrap();
+
+        assert_eq!(played.strategy, "mcts");
+        assert_eq!(played.character, CharacterType::Bixiette);
+        assert_eq!(played.seed, Some(9));
+        assert_eq!(played.weights, Some("w.txt".to_string()));
+        assert_eq!(played.log_level, LogLevel::Error);
+    }
+
+    #[test]
+    fn invalid_args_test() {
+        assert!(Config::from_args(args("--strategy nope"), |_| None).is_err());
+        assert!(Config::from_args(args("--seed"), |_| None).is_err());
+        assert!(Config::from_args(args("--colour red"), |_| None).is_err());
+        assert!(Config::from_args(args("stray"), |_| None).is_err());
+        assert!(Config::from_args(vec![], |_| Some("shouting".into())).is_err());
+    }
+}
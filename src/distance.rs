@@ -0,0 +1,241 @@
+//! Breadth-first distance maps over the walkable field
+use std::collections::VecDeque;
+
+use field::{Field, Point};
+use player::MoveType;
+use simple_matrix::Matrix;
+
+/// Shortest walking distances from a single source point.
+#[derive(Clone, Debug)]
+pub struct DistanceMap {
+    pub source: Point,
+    distances: Matrix<Option<usize>>,
+}
+
+impl DistanceMap {
+    /// Run a breadth-first search from `source`, honoring walls and gates.
+    pub fn new(field: &Field, source: Point) -> Self {
+        let mut distances = Matrix::filled(field.width(), field.height(), None);
+        let mut queue = VecDeque::new();
+
+        if field.is_walkable(source) {
+            distances[(source.x, source.y)] = Some(0);
+            queue.push_back((source, 0));
+        }
+
+        while let Some((point, distance)) = queue.pop_front() {
+            for next in field.neighbors(point) {
+                if distances[(next.x, next.y)].is_none() {
+                    distances[(next.x, next.y)] = Some(distance + 1);
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+
+        DistanceMap { source, distances }
+    }
+
+    /// Get the number of moves from the source to `to`, if it is reachable.
+    pub fn distance(&self, to: Point) -> Option<usize> {
+        self.distances.get(to.x, to.y).and_then(|d| *d)
+    }
+
+    /// Get the closest reachable point out of `points`, with its distance.
+    pub fn nearest<'a, I>(&self, points: I) -> Option<(Point, usize)>
+        where I: IntoIterator<Item = &'a Point>
+    {
+        nearest_by(points, |p| self.distance(p))
+    }
+
+    /// Get the moves along a shortest path from the source to `target`.
+    ///
+    /// The path is empty when `target` is the source, and `None` when the
+    /// target cannot be reached.
+    pub fn path_to(&self, field: &Field, target: Point) -> Option<Vec<MoveType>> {
+        let mut current = target;
+        let mut remaining = self.distance(target)?;
+        let mut path = Vec::with_capacity(remaining);
+
+        while remaining > 0 {
+            let (direction, previous) = field.neighbors(current)
+                .into_iter()
+                .filter(|&p| self.distance(p) == Some(remaining - 1))
+                .filter_map(|p| {
+                    field.moves_from(p)
+                        .into_iter()
+                        .find(|&(_, next)| next == current)
+                        .map(|(direction, _)| (direction, p))
+                })
+                .next()?;
+            path.push(direction);
+            current = previous;
+            remaining -= 1;
+        }
+
+        path.reverse();
+        Some(path)
+    }
+
+    /// Get the first move along a shortest path from the source to `target`.
+    pub fn first_move(&self, field: &Field, target: Point) -> Option<MoveType> {
+        self.path_to(field, target).and_then(|path| path.first().cloned())
+    }
+}
+
+/// Shortest walking distances between every pair of points.
+///
+/// Walls and gates never change during a game, so the table is built once
+/// and answers lookups in constant time.
+#[derive(Clone, Debug)]
+pub struct DistanceTable {
+    width: usize,
+    height: usize,
+    distances: Vec<u16>,
+}
+
+const UNREACHABLE: u16 = ::std::u16::MAX;
+
+impl DistanceTable {
+    /// Run a breadth-first search from every walkable point of the field.
+    pub fn new(field: &Field) -> Self {
+        let (width, height) = (field.width(), field.height());
+        let size = width * height;
+        let mut distances = vec![UNREACHABLE; size * size];
+
+        for from in 0..size {
+            let source = Point::new(from % width, from / width);
+            if !field.is_walkable(source) {
+                continue;
+            }
+            let map = DistanceMap::new(field, source);
+            let row = &mut distances[from * size..(from + 1) * size];
+            for (to, d) in row.iter_mut().enumerate() {
+                if let Some(n) = map.distance(Point::new(to % width, to / width)) {
+                    *d = n as u16;
+                }
+            }
+        }
+
+        DistanceTable { width, height, distances }
+    }
+
+    /// Check that the table was built for a field of this size.
+    pub fn fits(&self, field: &Field) -> bool {
+        self.width == field.width() && self.height == field.height()
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        if p.x < self.width && p.y < self.height {
+            Some(p.y * self.width + p.x)
+        } else {
+            None
+        }
+    }
+
+    /// Get the number of moves between two points, if there is a path.
+    pub fn distance(&self, from: Point, to: Point) -> Option<usize> {
+        let size = self.width * self.height;
+        match (self.index(from), self.index(to)) {
+            (Some(a), Some(b)) => {
+                match self.distances[a * size + b] {
+                    UNREACHABLE => None,
+                    d => Some(d as usize),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the closest point out of `points` reachable from `from`, with its distance.
+    pub fn nearest<'a, I>(&self, from: Point, points: I) -> Option<(Point, usize)>
+        where I: IntoIterator<Item = &'a Point>
+    {
+        nearest_by(points, |p| self.distance(from, p))
+    }
+
+    /// Get the first move along a shortest path between two points.
+    pub fn first_move(&self, field: &Field, from: Point, to: Point) -> Option<MoveType> {
+        let remaining = self.distance(from, to)?;
+        if remaining == 0 {
+            return None;
+        }
+        field.moves_from(from)
+            .into_iter()
+            .find(|&(_, next)| self.distance(next, to) == Some(remaining - 1))
+            .map(|(direction, _)| direction)
+    }
+}
+
+/// Get the point out of `points` with the smallest known distance.
+///
+/// Ties go to the point listed first.
+fn nearest_by<'a, I, F>(points: I, distance: F) -> Option<(Point, usize)>
+    where I: IntoIterator<Item = &'a Point>,
+          F: Fn(Point) -> Option<usize>
+{
+    points.into_iter()
+        .filter_map(|&p| distance(p).map(|d| (p, d)))
+        .fold(None, |best, (p, d)| match best {
+            Some((_, best_d)) if best_d <= d => best,
+            _ => Some((p, d)),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use distance::{DistanceMap, DistanceTable};
+    use field::{field_from, Point};
+    use player::MoveType;
+
+    #[test]
+    fn distance_test() {
+        let field = field_from(4, 3, "\
+            .,.,.,.,\
+            .,x,x,.,\
+            .,.,x,.");
+        let map = DistanceMap::new(&field, Point::new(0, 2));
+
+        assert_eq!(map.distance(Point::new(0, 2)), Some(0));
+        assert_eq!(map.distance(Point::new(1, 2)), Some(1));
+        assert_eq!(map.distance(Point::new(3, 2)), Some(7));
+        assert_eq!(map.distance(Point::new(1, 1)), None);
+        assert_eq!(map.nearest(&[Point::new(3, 0), Point::new(1, 2)]),
+                   Some((Point::new(1, 2), 1)));
+    }
+
+    #[test]
+    fn path_through_gate_test() {
+        let field = field_from(5, 3, "\
+            x,x,x,x,x,\
+            Gl,.,.,.,Gr,\
+            x,x,x,x,x");
+        let map = DistanceMap::new(&field, Point::new(1, 1));
+
+        assert_eq!(map.distance(Point::new(4, 1)), Some(2));
+        assert_eq!(map.path_to(&field, Point::new(4, 1)),
+                   Some(vec![MoveType::Left, MoveType::Left]));
+        assert_eq!(map.path_to(&field, Point::new(1, 1)), Some(vec![]));
+        assert_eq!(map.first_move(&field, Point::new(2, 1)), Some(MoveType::Right));
+    }
+
+    #[test]
+    fn table_matches_maps_test() {
+        let field = field_from(5, 3, "\
+            x,.,x,.,x,\
+            Gl,.,.,.,Gr,\
+            x,.,x,x,x");
+        let table = DistanceTable::new(&field);
+
+        for i in 0..15 {
+            let from = Point::new(i % 5, i / 5);
+            let map = DistanceMap::new(&field, from);
+            for j in 0..15 {
+                let to = Point::new(j % 5, j / 5);
+                assert_eq!(table.distance(from, to), map.distance(to));
+            }
+        }
+        assert_eq!(table.first_move(&field, Point::new(1, 2), Point::new(4, 1)),
+                   Some(MoveType::Up));
+        assert_eq!(table.distance(Point::new(5, 0), Point::new(1, 1)), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Predict where the bugs are heading
+//!
+//! The engine does not tell us how its bugs think, so each `AiType` is
+//! modelled on its name:
+//!
+//! * `Chase` heads for the closest player.
+//! * `Predict` heads for where the closest player will be a few moves from
+//!   now, assuming that player walks towards its nearest snippet.
+//! * `Lever` heads for the far side of the closest player, mirroring the
+//!   other player through it, to pin the closest player between the two.
+//! * `FarChase` heads for the player furthest away.
+//!
+//! Bugs step to whichever neighbour is closest to their target, breaking
+//! ties in `MoveType::DIRECTIONS` order.
+use distance::DistanceTable;
+use field::{AiType, Enemy, Field, Point};
+
+/// How many moves ahead a `Predict` bug looks.
+const PREDICT_AHEAD: usize = 4;
+
+pub struct BugPredictor<'a> {
+    field: &'a Field,
+    distances: &'a DistanceTable,
+}
+
+impl<'a> BugPredictor<'a> {
+    pub fn new(field: &'a Field, distances: &'a DistanceTable) -> Self {
+        BugPredictor { field, distances }
+    }
+
+    /// Get the point a bug is heading for, given where the players are.
+    pub fn target(&self, enemy: &Enemy, players: &[Point]) -> Option<Point> {
+        let closest = self.by_distance(enemy.point, players).first().cloned()?;
+        match enemy.ai {
+            AiType::Chase => Some(closest),
+            AiType::Predict => Some(self.predict_player(closest)),
+            AiType::Lever => {
+                let other = players.iter().cloned().find(|&p| p != closest);
+                Some(other.map_or(closest, |other| self.mirror(other, closest)))
+            }
+            AiType::FarChase => self.by_distance(enemy.point, players).last().cloned(),
+        }
+    }
+
+    /// Get the cell a bug moves to next.
+    pub fn next_step(&self, enemy: &Enemy, players: &[Point]) -> Point {
+        let target = match self.target(enemy, players) {
+            Some(t) => t,
+            None => return enemy.point,
+        };
+        if enemy.point == target {
+            return enemy.point;
+        }
+        let distances = self.distances;
+        self.field
+            .neighbors(enemy.point)
+            .into_iter()
+            .filter_map(|next| distances.distance(next, target).map(|d| (next, d)))
+            .fold(None, |best, (next, d)| match best {
+                Some((_, best_d)) if best_d <= d => best,
+                _ => Some((next, d)),
+            })
+            .map_or(enemy.point, |(next, _)| next)
+    }
+
+    /// Get the cells a bug walks through over the next `steps` moves.
+    ///
+    /// The players are assumed to stay where they are.
+    pub fn trajectory(&self, enemy: &Enemy, players: &[Point], steps: usize) -> Vec<Point> {
+        let mut bug = *enemy;
+        let mut path = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            bug.point = self.next_step(&bug, players);
+            path.push(bug.point);
+        }
+        path
+    }
+
+    /// Get the reachable players ordered from closest to furthest.
+    fn by_distance(&self, from: Point, players: &[Point]) -> Vec<Point> {
+        let mut reachable: Vec<(Point, usize)> = players.iter()
+            .filter_map(|&p| self.distances.distance(from, p).map(|d| (p, d)))
+            .collect();
+        reachable.sort_by_key(|&(_, d)| d);
+        reachable.into_iter().map(|(p, _)| p).collect()
+    }
+
+    /// Guess where a player will be if it walks towards its nearest snippet.
+    fn predict_player(&self, player: Point) -> Point {
+        let snippet = self.distances.nearest(player, &self.field.snippet_positions);
+        let mut current = player;
+        if let Some((target, _)) = snippet {
+            for _ in 0..PREDICT_AHEAD {
+                match self.distances.first_move(self.field, current, target) {
+                    Some(m) => current = self.field.step(current, m).unwrap_or(current),
+                    None => break,
+                }
+            }
+        }
+        current
+    }
+
+    /// Reflect `from` through `pivot`, snapping to the nearest walkable cell.
+    fn mirror(&self, from: Point, pivot: Point) -> Point {
+        let reflect = |a: usize, b: usize| (2 * b as isize - a as isize).max(0) as usize;
+        let x = reflect(from.x, pivot.x).min(self.field.width().saturating_sub(1));
+        let y = reflect(from.y, pivot.y).min(self.field.height().saturating_sub(1));
+        let ideal = Point::new(x, y);
+        let manhattan = |p: Point| {
+            (p.x as isize - ideal.x as isize).abs() + (p.y as isize - ideal.y as isize).abs()
+        };
+
+        (0..self.field.height())
+            .flat_map(|y| (0..self.field.width()).map(move |x| Point::new(x, y)))
+            .filter(|&p| self.distances.distance(pivot, p).is_some())
+            .min_by_key(|&p| manhattan(p))
+            .unwrap_or(pivot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use distance::DistanceTable;
+    use enemy::BugPredictor;
+    use field::{field_from, AiType, Enemy, Point};
+
+    #[test]
+    fn chase_and_far_chase_test() {
+        let field = field_from(7, 1, "P0,.,.,E0,.,.,P1");
+        let table = DistanceTable::new(&field);
+        let predictor = BugPredictor::new(&field, &table);
+        let players = [Point::new(0, 0), Point::new(6, 0)];
+
+        let chase = Enemy { point: Point::new(2, 0), ai: AiType::Chase };
+        assert_eq!(predictor.next_step(&chase, &players), Point::new(1, 0));
+
+        let far = Enemy { point: Point::new(2, 0), ai: AiType::FarChase };
+        assert_eq!(predictor.trajectory(&far, &players, 2),
+                   vec![Point::new(3, 0), Point::new(4, 0)]);
+    }
+
+    #[test]
+    fn predict_and_lever_test() {
+        let field = field_from(9, 1, "E1,.,P0,.,.,.,.,.,C");
+        let table = DistanceTable::new(&field);
+        let predictor = BugPredictor::new(&field, &table);
+        let players = [Point::new(2, 0), Point::new(0, 0)];
+
+        let predict = Enemy { point: Point::new(0, 0), ai: AiType::Predict };
+        assert_eq!(predictor.target(&predict, &players[..1]), Some(Point::new(6, 0)));
+
+        let lever = Enemy { point: Point::new(1, 0), ai: AiType::Lever };
+        assert_eq!(predictor.target(&lever, &players), Some(Point::new(4, 0)));
+    }
+}
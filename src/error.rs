@@ -1,18 +1,26 @@
 use std::error::Error as StdError;
 use std::io::Error as IoError;
 use std::fmt;
+use std::num::ParseIntError;
 use std::any::Any;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
-type BoxAny = Box<Any + Send>;
+type BoxAny = Box<dyn Any + Send>;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     IoError(IoError),
-    ParseError(ParseErrorKind),
+    ParseError(ParseError),
     UnknownError(BoxAny),
     UnintentionalBreak,
     PlayerNotFound(String),
+    FieldSizeMismatch(usize, usize),
+    ReplayMismatch(usize),
+    UnknownStrategy(String),
+    InvalidArgument(String),
+    InvalidWeights(usize, String),
+    InvalidBomb(String),
 }
 
 #[derive(Debug)]
@@ -20,7 +28,62 @@ pub enum ParseErrorKind {
     UnknownCommand,
     Incomplete,
     InvalidCellType,
-    NumberFormat(BoxAny),
+    NumberFormat(ParseIntError),
+}
+
+/// What went wrong parsing input, and where.
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The raw line being parsed.
+    pub line: Option<String>,
+    /// The command the line starts with, like `update game field`.
+    pub command: Option<String>,
+    /// The index and text of the field cell that failed.
+    pub cell: Option<(usize, String)>,
+}
+
+impl From<ParseErrorKind> for Error {
+    fn from(kind: ParseErrorKind) -> Self {
+        Error::ParseError(ParseError {
+            kind,
+            line: None,
+            command: None,
+            cell: None,
+        })
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(error: ParseIntError) -> Self {
+        Error::from(ParseErrorKind::NumberFormat(error))
+    }
+}
+
+impl Error {
+    /// Note the line, and the command it starts with, that a parse error
+    /// came from. Context that is already there is kept.
+    pub fn in_line(self, line: &str, command: &str) -> Self {
+        match self {
+            Error::ParseError(mut e) => {
+                e.line = e.line.or_else(|| Some(line.to_string()));
+                e.command = e.command.or_else(|| Some(command.to_string()));
+                Error::ParseError(e)
+            }
+            e => e,
+        }
+    }
+
+    /// Note the field cell a parse error came from.
+    pub fn in_cell(self, index: usize, token: &str) -> Self {
+        match self {
+            Error::ParseError(mut e) => {
+                e.cell = e.cell.or_else(|| Some((index, token.to_string())));
+                Error::ParseError(e)
+            }
+            e => e,
+        }
+    }
 }
 
 impl From<BoxAny> for Error {
@@ -43,11 +106,19 @@ impl StdError for Error {
             Error::UnknownError(_) => "unknown error",
             Error::UnintentionalBreak => "unintentional break error",
             Error::PlayerNotFound(_) => "player not found error",
+            Error::FieldSizeMismatch(_, _) => "field size mismatch error",
+            Error::ReplayMismatch(_) => "replay mismatch error",
+            Error::UnknownStrategy(_) => "unknown strategy error",
+            Error::InvalidArgument(_) => "invalid argument error",
+            Error::InvalidWeights(_, _) => "invalid weights error",
+            Error::InvalidBomb(_) => "invalid bomb error",
         }
     }
 
-    fn cause(&self) -> Option<&StdError> {
+    /// Get the error behind this one, which `cause` returns as well.
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match *self {
+            Error::ParseError(ParseError { kind: ParseErrorKind::NumberFormat(ref e), .. }) => Some(e),
             _ => None,
         }
     }
@@ -59,8 +130,24 @@ impl fmt::Display for ParseErrorKind {
             ParseErrorKind::UnknownCommand => write!(fmt, "Unknown command"),
             ParseErrorKind::Incomplete => write!(fmt, "Incomplete command"),
             ParseErrorKind::InvalidCellType => write!(fmt, "Invalid field cell type"),
-            ParseErrorKind::NumberFormat(ref e) => write!(fmt, "Could not parse value {:?}", e),
+            ParseErrorKind::NumberFormat(ref e) => write!(fmt, "Could not parse value {}", e),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.kind)?;
+        if let Some((index, ref token)) = self.cell {
+            write!(fmt, " at cell {} {:?}", index, token)?;
+        }
+        if let Some(ref command) = self.command {
+            write!(fmt, " in {:?}", command)?;
+        }
+        if let Some(ref line) = self.line {
+            write!(fmt, " from line {:?}", line)?;
         }
+        Ok(())
     }
 }
 
@@ -72,6 +159,16 @@ impl fmt::Display for Error {
             Error::UnknownError(ref msg) => write!(fmt, "Unknown error {:?}", msg),
             Error::UnintentionalBreak => write!(fmt, "Unintentional break error"),
             Error::PlayerNotFound(ref name) => write!(fmt, "Player not found error {}", name),
+            Error::FieldSizeMismatch(expected, actual) => {
+                write!(fmt, "Field size mismatch error expected {} cells, got {}", expected, actual)
+            }
+            Error::ReplayMismatch(count) => write!(fmt, "Replay mismatch error {} replies differ", count),
+            Error::UnknownStrategy(ref name) => write!(fmt, "Unknown strategy error {}", name),
+            Error::InvalidArgument(ref arg) => write!(fmt, "Invalid argument error {}", arg),
+            Error::InvalidWeights(line, ref text) => {
+                write!(fmt, "Invalid weights error on line {}: {}", line, text)
+            }
+            Error::InvalidBomb(ref reason) => write!(fmt, "Invalid bomb error {}", reason),
         }
     }
 }
@@ -0,0 +1,203 @@
+//! Score simulated positions with tunable weights
+//!
+//! The weights are read from a plain text file of `key=value` lines, where
+//! blank lines and lines starting with `#` are skipped. Keys missing from the
+//! file keep the values built into the bot from `default_weights.txt`.
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+use blast::blast_cells;
+use distance::DistanceTable;
+use error::*;
+use field::Field;
+use player::MAX_BOMB_TICKS;
+use simulator::{GameState, ME, OPPONENT};
+
+/// The weights built into the bot.
+pub const DEFAULT_WEIGHTS: &str = include_str!("default_weights.txt");
+
+/// The score of a position where one player is dead.
+pub const WIN: i32 = 100_000;
+
+/// Bugs further away than this are no danger yet.
+const DANGER_RADIUS: usize = 3;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Weights {
+    /// Per snippet held more than the opponent.
+    pub snippet: i32,
+    /// Per bomb held more than the opponent.
+    pub bomb: i32,
+    /// Per step from us to the closest snippet.
+    pub snippet_distance: i32,
+    /// Per step from the opponent to the closest snippet.
+    pub opponent_snippet_distance: i32,
+    /// Per step a bug is inside `DANGER_RADIUS` of us.
+    pub bug_danger: i32,
+    /// Per round a bomb we stand in the way of is closer to going off.
+    pub bomb_danger: i32,
+}
+
+impl Weights {
+    /// Read weights from a file on top of the built in ones.
+    pub fn load(path: &str) -> Result<Weights> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        text.parse()
+    }
+
+    /// Set the weights given in `text`, leaving the others alone.
+    pub fn apply(&mut self, text: &str) -> Result<()> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::InvalidWeights(number + 1, line.to_string());
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(invalid()),
+            };
+            let value = value.parse().map_err(|_| invalid())?;
+            match key {
+                "snippet" => self.snippet = value,
+                "bomb" => self.bomb = value,
+                "snippet_distance" => self.snippet_distance = value,
+                "opponent_snippet_distance" => self.opponent_snippet_distance = value,
+                "bug_danger" => self.bug_danger = value,
+                "bomb_danger" => self.bomb_danger = value,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the weights built into the bot.
+    pub fn builtin() -> Weights {
+        let mut weights = Weights::default();
+        weights.apply(DEFAULT_WEIGHTS).expect("built in weights");
+        weights
+    }
+}
+
+impl FromStr for Weights {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut weights = Weights::builtin();
+        weights.apply(s)?;
+        Ok(weights)
+    }
+}
+
+/// Scores positions from our point of view, higher is better.
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    pub weights: Weights,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new(Weights::builtin())
+    }
+}
+
+impl Evaluator {
+    pub fn new(weights: Weights) -> Self {
+        Evaluator { weights }
+    }
+
+    pub fn evaluate(&self, state: &GameState, field: &Field, distances: &DistanceTable) -> i32 {
+        let (me, them) = (&state.players[ME], &state.players[OPPONENT]);
+        match (me.alive, them.alive) {
+            (false, _) => return -WIN,
+            (true, false) => return WIN,
+            _ => {}
+        }
+
+        let snippet_distance = |player: usize| {
+            state.players[player]
+                .point
+                .and_then(|p| distances.nearest(p, &state.snippets))
+                .map_or(0, |(_, d)| d as i32)
+        };
+
+        let w = &self.weights;
+        w.snippet * (me.snippets - them.snippets) + w.bomb * (me.bombs - them.bombs) -
+        w.snippet_distance * snippet_distance(ME) +
+        w.opponent_snippet_distance * snippet_distance(OPPONENT) -
+        w.bug_danger * bug_danger(state, distances) -
+        w.bomb_danger * bomb_danger(state, field)
+    }
+}
+
+/// Add up how far inside `DANGER_RADIUS` of us each bug is.
+fn bug_danger(state: &GameState, distances: &DistanceTable) -> i32 {
+    let me = match state.players[ME].point {
+        Some(p) => p,
+        None => return 0,
+    };
+    state.enemies
+        .iter()
+        .filter_map(|enemy| distances.distance(enemy.point, me))
+        .filter(|&d| d <= DANGER_RADIUS)
+        .map(|d| (DANGER_RADIUS + 1 - d) as i32)
+        .sum()
+}
+
+/// Add up how soon each bomb whose blast would reach us goes off.
+fn bomb_danger(state: &GameState, field: &Field) -> i32 {
+    let me = match state.players[ME].point {
+        Some(p) => p,
+        None => return 0,
+    };
+    state.ticking_bombs
+        .iter()
+        .filter(|bomb| blast_cells(field, bomb.point).contains(&me))
+        .map(|bomb| (MAX_BOMB_TICKS + 1 - bomb.ticks).max(1))
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use eval::{Evaluator, Weights};
+    use simulator::GameState;
+    use strategy::TestGame;
+
+    /// Score a one row position with the built in weights.
+    fn score(cells: &str) -> i32 {
+        let game = TestGame::new(cells.split(',').count(), 1, cells);
+        let state = GameState::new(&game.field, &game.players, &game.settings);
+        Evaluator::default().evaluate(&state, &game.field, &game.distances)
+    }
+
+    #[test]
+    fn evaluate_test() {
+        // Closer to a snippet is better, and so is the opponent being further
+        assert!(score("C,P0,.,.,.,.,P1") > score("C,.,.,P0,.,.,P1"));
+        assert!(score("C,.,.,P0,.,.,P1") > score("C,P1,.,P0,.,.,."));
+
+        // Bugs count once they are close, more the closer they are
+        assert_eq!(score("P0,.,.,.,.,E0,P1"), score("P0,.,.,.,.,.,P1"));
+        assert!(score("P0,.,.,E0,.,.,P1") < score("P0,.,.,.,.,E0,P1"));
+        assert!(score("P0,E0,.,.,.,.,P1") < score("P0,.,.,E0,.,.,P1"));
+
+        // Standing in a blast is worse the sooner the bomb goes off
+        assert!(score("P0,.,B5,.,.,.,P1") < score("P0,.,.,.,.,.,P1"));
+        assert!(score("P0,.,B1,.,.,.,P1") < score("P0,.,B5,.,.,.,P1"));
+    }
+
+    #[test]
+    fn parse_weights_test() {
+        let builtin = Weights::builtin();
+        assert_eq!(builtin.snippet, 100);
+
+        let weights: Weights = "# tuned\n\nbomb = 35\nbug_danger=-1\n".parse().unwrap();
+        assert_eq!(weights, Weights { bomb: 35, bug_danger: -1, ..builtin });
+
+        assert!("bomb".parse::<Weights>().is_err());
+        assert!("bomb=lots".parse::<Weights>().is_err());
+        assert!("bombs=3".parse::<Weights>().is_err());
+    }
+}
@@ -1,5 +1,8 @@
+use std::fmt;
+
 use simple_matrix::Matrix;
 use error::{Error, ParseErrorKind, Result};
+use player::MoveType;
 
 #[derive(Debug)]
 pub struct Field {
@@ -8,18 +11,20 @@ pub struct Field {
     pub field: Matrix<Cell>,
     pub player_position: Option<Point>,
     pub opponent_position: Option<Point>,
-    pub enemy_positions: Vec<Point>,
+    pub enemies: Vec<Enemy>,
     pub snippet_positions: Vec<Point>,
+    /// Bombs lying on the field waiting to be picked up.
     pub bomb_positions: Vec<Point>,
-    pub ticking_bomb_positions: Vec<Point>,
+    /// Bombs that have been dropped and are counting down.
+    pub ticking_bombs: Vec<TickingBomb>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Cell {
     cell_items: Vec<CellItem>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum CellItem {
     Empty,
     Inaccessible,
@@ -29,15 +34,17 @@ pub enum CellItem {
     Enemy(AiType),
     Bomb(i32),
     CodeSnippet,
+    /// A cell code this bot doesn't know yet, kept as it was sent.
+    Unknown(String),
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GateDirection {
     Left,
     Right,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AiType {
     Chase,
     Predict,
@@ -45,12 +52,32 @@ pub enum AiType {
     FarChase,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// A bug and the way it picks its target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Enemy {
+    pub point: Point,
+    pub ai: AiType,
+}
+
+/// A dropped bomb and the number of rounds until it explodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TickingBomb {
+    pub point: Point,
+    pub ticks: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
 }
 
+impl Point {
+    pub fn new(x: usize, y: usize) -> Self {
+        Point { x, y }
+    }
+}
+
 impl Field {
     pub fn new(width: usize, height: usize) -> Self {
         Field {
@@ -59,10 +86,10 @@ impl Field {
             field: Matrix::new(width, height),
             player_position: None,
             opponent_position: None,
-            enemy_positions: Vec::new(),
+            enemies: Vec::new(),
             snippet_positions: Vec::new(),
             bomb_positions: Vec::new(),
-            ticking_bomb_positions: Vec::new(),
+            ticking_bombs: Vec::new(),
         }
     }
 
@@ -72,8 +99,20 @@ impl Field {
         Point { x, y }
     }
 
-    pub fn update_field(&mut self, field: Vec<Cell>) {
-        // TODO update other Field values, too
+    /// Replace the field cells and rebuild everything derived from them.
+    pub fn update_field(&mut self, field: Vec<Cell>) -> Result<()> {
+        let expected = self.width() * self.height();
+        if field.len() != expected {
+            return Err(Error::FieldSizeMismatch(expected, field.len()));
+        }
+
+        self.player_position = None;
+        self.opponent_position = None;
+        self.enemies.clear();
+        self.snippet_positions.clear();
+        self.bomb_positions.clear();
+        self.ticking_bombs.clear();
+
         for (i, cell) in field.iter().enumerate() {
             let point = self.get_point(i);
             for item in &cell.cell_items {
@@ -86,12 +125,14 @@ impl Field {
                             self.opponent_position = Some(point);
                         }
                     }
-                    CellItem::Enemy(enemy) => {
-                        self.enemy_positions.push(point);
+                    CellItem::Enemy(ai) => {
+                        self.enemies.push(Enemy { point, ai });
                     }
-                    CellItem::Bomb(n) => {
+                    CellItem::Bomb(ticks) if ticks < 0 => {
                         self.bomb_positions.push(point);
-                        self.ticking_bomb_positions.push(point);
+                    }
+                    CellItem::Bomb(ticks) => {
+                        self.ticking_bombs.push(TickingBomb { point, ticks });
                     }
                     CellItem::CodeSnippet => {
                         self.snippet_positions.push(point);
@@ -102,6 +143,7 @@ impl Field {
         }
 
         self.field.m = field;
+        Ok(())
     }
 
     pub fn set_width(&mut self, width: usize) {
@@ -111,24 +153,191 @@ impl Field {
     pub fn set_height(&mut self, height: usize) {
         self.field.height = height;
     }
+
+    pub fn width(&self) -> usize {
+        self.field.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.field.height
+    }
+
+    /// Get the cell at the given point, if it is on the field.
+    pub fn cell(&self, point: Point) -> Option<&Cell> {
+        self.field.get(point.x, point.y)
+    }
+
+    /// Check that the point is on the field and not a wall.
+    pub fn is_walkable(&self, point: Point) -> bool {
+        match self.cell(point) {
+            Some(cell) => !cell.contains(CellItem::Inaccessible),
+            None => false,
+        }
+    }
+
+    /// Get the point reached by moving from `point` in the given direction.
+    ///
+    /// Returns `None` when the move would leave the field or hit a wall.
+    /// Walking out of a gate in its direction teleports to the opposite gate.
+    pub fn step(&self, point: Point, direction: MoveType) -> Option<Point> {
+        if !self.is_walkable(point) {
+            return None;
+        }
+        if let Some(gate) = self.cell(point).and_then(Cell::gate) {
+            let through_gate = matches!((gate, direction),
+                                        (GateDirection::Left, MoveType::Left) |
+                                        (GateDirection::Right, MoveType::Right));
+            if through_gate {
+                return self.find_gate_exit(point, gate);
+            }
+        }
+
+        let Point { x, y } = point;
+        let next = match direction {
+            MoveType::Up if y > 0 => Point::new(x, y - 1),
+            MoveType::Down => Point::new(x, y + 1),
+            MoveType::Left if x > 0 => Point::new(x - 1, y),
+            MoveType::Right => Point::new(x + 1, y),
+            MoveType::Pass => point,
+            _ => return None,
+        };
+
+        if self.is_walkable(next) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// Get every point reachable in one move from `point`, paired with the
+    /// move that reaches it. Standing still is not included.
+    pub fn moves_from(&self, point: Point) -> Vec<(MoveType, Point)> {
+        MoveType::DIRECTIONS
+            .iter()
+            .filter_map(|&direction| self.step(point, direction).map(|next| (direction, next)))
+            .collect()
+    }
+
+    /// Get the moves the engine accepts from `point`, standing still included.
+    pub fn legal_moves(&self, point: Point) -> Vec<MoveType> {
+        let mut moves = vec![MoveType::Pass];
+        moves.extend(self.moves_from(point).into_iter().map(|(direction, _)| direction));
+        moves
+    }
+
+    /// Get every point reachable in one move from `point`.
+    pub fn neighbors(&self, point: Point) -> Vec<Point> {
+        self.moves_from(point)
+            .into_iter()
+            .map(|(_, next)| next)
+            .collect()
+    }
+
+    /// Find the gate opposite the one at `from`, preferring the same row.
+    fn find_gate_exit(&self, from: Point, gate: GateDirection) -> Option<Point> {
+        let exit = match gate {
+            GateDirection::Left => GateDirection::Right,
+            GateDirection::Right => GateDirection::Left,
+        };
+        let row = (0..self.width()).map(|x| Point::new(x, from.y));
+        let everywhere = (0..self.field.m.len()).map(|i| self.get_point(i));
+        row.chain(everywhere)
+            .find(|&p| self.cell(p).and_then(Cell::gate) == Some(exit))
+    }
 }
 
 impl Cell {
     pub fn new() -> Cell {
         Cell { cell_items: Vec::new() }
     }
+
+    /// Get the items in this cell.
+    pub fn items(&self) -> &[CellItem] {
+        &self.cell_items
+    }
+
+    pub fn contains(&self, item: CellItem) -> bool {
+        self.cell_items.contains(&item)
+    }
+
+    /// Get the direction of the gate in this cell, if there is one.
+    pub fn gate(&self) -> Option<GateDirection> {
+        self.cell_items
+            .iter()
+            .filter_map(|item| match *item {
+                CellItem::Gate(direction) => Some(direction),
+                _ => None,
+            })
+            .next()
+    }
 }
 
 pub fn parse_field(s: &str) -> Result<Vec<Cell>> {
-    s.split(",")
-        .map(|cell| {
-            cell.split(";")
-                .map(|cell_type| cell_type.parse::<CellItem>())
+    s.split(',')
+        .enumerate()
+        .map(|(index, cell)| {
+            cell.split(';')
+                .map(|cell_type| cell_type.parse::<CellItem>().map_err(|e| e.in_cell(index, cell_type)))
                 .collect()
         })
         .collect()
 }
 
+/// Build a field from rows of cell strings.
+#[cfg(test)]
+pub fn field_from(width: usize, height: usize, cells: &str) -> Field {
+    let mut field = Field::new(width, height);
+    field.update_field(parse_field(cells).unwrap()).unwrap();
+    field
+}
+
+/// Write cells the way the engine sends them, the inverse of `parse_field`.
+pub fn format_field(cells: &[Cell]) -> String {
+    cells.iter()
+        .map(Cell::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for Cell {
+    /// A cell without items is written as empty.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.cell_items.is_empty() {
+            return write!(f, "{}", CellItem::Empty);
+        }
+        for (i, item) in self.cell_items.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CellItem {
+    /// Bombs waiting to be picked up have a negative count and no number.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CellItem::Empty => write!(f, "."),
+            CellItem::Inaccessible => write!(f, "x"),
+            CellItem::Player(id) => write!(f, "P{}", id),
+            CellItem::Spawn(None) => write!(f, "S"),
+            CellItem::Spawn(Some(n)) => write!(f, "S{}", n),
+            CellItem::Gate(GateDirection::Left) => write!(f, "Gl"),
+            CellItem::Gate(GateDirection::Right) => write!(f, "Gr"),
+            CellItem::Enemy(AiType::Chase) => write!(f, "E0"),
+            CellItem::Enemy(AiType::Predict) => write!(f, "E1"),
+            CellItem::Enemy(AiType::Lever) => write!(f, "E2"),
+            CellItem::Enemy(AiType::FarChase) => write!(f, "E3"),
+            CellItem::Bomb(n) if n < 0 => write!(f, "B"),
+            CellItem::Bomb(n) => write!(f, "B{}", n),
+            CellItem::CodeSnippet => write!(f, "C"),
+            CellItem::Unknown(ref code) => write!(f, "{}", code),
+        }
+    }
+}
+
 impl ::std::iter::FromIterator<CellItem> for Cell {
     fn from_iter<I: IntoIterator<Item = CellItem>>(iter: I) -> Self {
         let mut c = Cell::new();
@@ -143,7 +352,11 @@ impl ::std::str::FromStr for CellItem {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let cell_type = s.split_at(1);
+        let first = match s.chars().next() {
+            Some(c) => c,
+            None => return Err(Error::from(ParseErrorKind::InvalidCellType)),
+        };
+        let cell_type = s.split_at(first.len_utf8());
         match cell_type {
             (".", _) => Ok(CellItem::Empty),
             ("x", _) => Ok(CellItem::Inaccessible),
@@ -151,7 +364,7 @@ impl ::std::str::FromStr for CellItem {
                 // Player<id>
                 match v.parse::<i32>() {
                     Ok(n) => Ok(CellItem::Player(n)),
-                    Err(e) => Err(Error::ParseError(ParseErrorKind::NumberFormat(Box::new(e)))),
+                    Err(e) => Err(Error::from(e)),
                 }
             }
             ("S", "") => Ok(CellItem::Spawn(None)),
@@ -159,7 +372,7 @@ impl ::std::str::FromStr for CellItem {
                 // Bug spawn point<rounds_until>
                 match v.parse::<i32>() {
                     Ok(n) => Ok(CellItem::Spawn(Some(n))),
-                    Err(e) => Err(Error::ParseError(ParseErrorKind::NumberFormat(Box::new(e)))),
+                    Err(e) => Err(Error::from(e)),
                 }
             }
             ("G", "l") => Ok(CellItem::Gate(GateDirection::Left)),
@@ -175,21 +388,26 @@ impl ::std::str::FromStr for CellItem {
                 // Bomb<rounds_until_detonate>
                 match v.parse::<i32>() {
                     Ok(n) => Ok(CellItem::Bomb(n)),
-                    Err(e) => Err(Error::ParseError(ParseErrorKind::NumberFormat(Box::new(e)))),
+                    Err(e) => Err(Error::from(e)),
                 }
             }
             ("C", "") => {
                 // Code Snippet
                 Ok(CellItem::CodeSnippet)
             }
-            _ => Err(Error::ParseError(ParseErrorKind::InvalidCellType)),
+            _ => Ok(CellItem::Unknown(s.into())),
         }
     }
 }
 
 #[cfg(test)]
-mod Test {
-    use field::{parse_field, CellItem, AiType, GateDirection};
+mod test {
+    use rand::{Rng, SeedableRng, StdRng};
+
+    use field::{field_from, format_field, parse_field, Cell, CellItem, AiType, Enemy, GateDirection,
+                Point, TickingBomb};
+    use player::MoveType;
+
     #[test]
     fn parse_field_test() {
         let field_str = ".,x,P0,S,S2,Gl,E0,B3,C";
@@ -203,6 +421,103 @@ mod Test {
                                   CellItem::Enemy(AiType::Chase),
                                   CellItem::Bomb(3),
                                   CellItem::CodeSnippet];
-        let actual_cells = parse_field(field_str);
+        let actual_cells = parse_field(field_str).unwrap();
+        let expected_cells: Vec<Cell> = expected_cells.into_iter()
+            .map(|item| Some(item).into_iter().collect())
+            .collect();
+        assert_eq!(actual_cells, expected_cells);
+    }
+
+    fn random_item(rng: &mut StdRng) -> CellItem {
+        let ais = [AiType::Chase, AiType::Predict, AiType::Lever, AiType::FarChase];
+        match rng.gen_range(0, 9) {
+            0 => CellItem::Empty,
+            1 => CellItem::Inaccessible,
+            2 => CellItem::Player(rng.gen_range(0, 2)),
+            3 => CellItem::Spawn(None),
+            4 => CellItem::Spawn(Some(rng.gen_range(0, 10))),
+            5 => CellItem::Gate(*rng.choose(&[GateDirection::Left, GateDirection::Right]).unwrap()),
+            6 => CellItem::Enemy(*rng.choose(&ais).unwrap()),
+            7 => CellItem::Bomb(rng.gen_range(-1, 6)),
+            _ => CellItem::CodeSnippet,
+        }
+    }
+
+    #[test]
+    fn format_field_round_trip_test() {
+        let mut rng: StdRng = SeedableRng::from_seed(&[23][..]);
+        for _ in 0..200 {
+            let cells: Vec<Cell> = (0..rng.gen_range(1, 40))
+                .map(|_| (0..rng.gen_range(1, 4)).map(|_| random_item(&mut rng)).collect())
+                .collect();
+            let text = format_field(&cells);
+            assert_eq!(parse_field(&text).unwrap(), cells, "{}", text);
+        }
+
+        // Pickups are written without a count and read back as -1
+        let pickup: Cell = Some(CellItem::Bomb(-1)).into_iter().collect();
+        assert_eq!(format_field(&[pickup, Cell::new()]), "B,.");
+    }
+
+    #[test]
+    fn neighbors_test() {
+        let field = field_from(3, 3, "\
+            .,x,.,\
+            .,.,.,\
+            x,.,x");
+
+        let mut neighbors = field.neighbors(Point::new(1, 1));
+        neighbors.sort_by_key(|p| (p.y, p.x));
+        assert_eq!(neighbors,
+                   vec![Point::new(0, 1), Point::new(2, 1), Point::new(1, 2)]);
+
+        assert_eq!(field.neighbors(Point::new(0, 0)), vec![Point::new(0, 1)]);
+        assert!(field.neighbors(Point::new(1, 0)).is_empty());
+        assert_eq!(field.step(Point::new(2, 1), MoveType::Right), None);
+        assert_eq!(field.step(Point::new(1, 2), MoveType::Down), None);
+    }
+
+    #[test]
+    fn gate_wrap_test() {
+        let field = field_from(4, 3, "\
+            x,x,x,x,\
+            Gl,.,.,Gr,\
+            x,x,x,x");
+
+        assert_eq!(field.step(Point::new(0, 1), MoveType::Left),
+                   Some(Point::new(3, 1)));
+        assert_eq!(field.step(Point::new(3, 1), MoveType::Right),
+                   Some(Point::new(0, 1)));
+        assert_eq!(field.step(Point::new(0, 1), MoveType::Right),
+                   Some(Point::new(1, 1)));
+        assert!(field.neighbors(Point::new(3, 1)).contains(&Point::new(0, 1)));
+        assert_eq!(field.legal_moves(Point::new(0, 1)),
+                   vec![MoveType::Pass, MoveType::Left, MoveType::Right]);
+    }
+
+    #[test]
+    fn update_field_resets_test() {
+        let mut field = field_from(3, 1, "P0;C,E1,P1");
+        assert_eq!(field.player_position, Some(Point::new(0, 0)));
+        assert_eq!(field.snippet_positions.len(), 1);
+
+        field.update_field(parse_field(".,P0,E2").unwrap()).unwrap();
+        assert_eq!(field.player_position, Some(Point::new(1, 0)));
+        assert_eq!(field.opponent_position, None);
+        assert!(field.snippet_positions.is_empty());
+        assert_eq!(field.enemies, vec![Enemy { point: Point::new(2, 0), ai: AiType::Lever }]);
+
+        assert!(field.update_field(parse_field(".,.").unwrap()).is_err());
+        assert_eq!(field.player_position, Some(Point::new(1, 0)));
+    }
+
+    #[test]
+    fn bomb_kinds_test() {
+        let field = field_from(3, 1, "B,B3,.;B1");
+
+        assert_eq!(field.bomb_positions, vec![Point::new(0, 0)]);
+        assert_eq!(field.ticking_bombs,
+                   vec![TickingBomb { point: Point::new(1, 0), ticks: 3 },
+                        TickingBomb { point: Point::new(2, 0), ticks: 1 }]);
     }
 }
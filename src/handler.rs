@@ -1,14 +1,140 @@
 //! Where the magic happens
+use std::any::Any;
+use std::io::{BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
 use error::*;
+use bomb::{check_escape, plan_bomb};
 use bot::BotState;
-use field::*;
+use config::LogLevel;
 use player::*;
 use message::*;
+use distance::DistanceTable;
+use field::{Cell, CellItem};
+use strategy::{fallback_move, guard_move, GameView};
+use timer::{self, Deadline};
+use transcript::Recorder;
+use transport::Transport;
+use watchdog::{Answer, Watchdog};
+
+/// Answer the engine's lines from `transport` until they run out.
+///
+/// Every move request gets a reply in time, falling back to a safe move
+/// when the strategy fails, panics or runs late. Failures are written to
+/// the transport's diagnostics as `log_level` allows, and everything sent
+/// either way goes into the `recorder`.
+pub fn run<T, R>(transport: T, bot: &BotState, log_level: LogLevel, mut recorder: Option<Recorder<R>>) -> Result<()>
+    where T: Transport,
+          R: Write
+{
+    let (lines, replies, mut err) = transport.split();
+    let watchdog = Watchdog::new(replies);
+
+    for line in lines.lines() {
+        let line = line?;
+        if let Some(ref mut recorder) = recorder {
+            recorder.input(bot.settings.borrow().round, &line)?;
+        }
+
+        // Everything from parsing on runs guarded, and a fallback is armed
+        // before a move so that one gets sent whatever happens
+        let mut time_to_respond = None;
+        let reply = match panic::catch_unwind(AssertUnwindSafe(|| {
+            let message = line.parse::<Message>()?;
+            if !bot.strict && log_level >= LogLevel::Info {
+                for kind_and_key in unknown_keys(&message) {
+                    if !bot.unknown.borrow().contains_key(&kind_and_key) {
+                        writeln!(err, "Keeping unknown {} {}", kind_and_key.0, kind_and_key.1)?;
+                    }
+                }
+            }
+            if let Message::Action(Action::Move { time_to_respond: ttr }) = message {
+                time_to_respond = Some(ttr);
+                let (fallback, limit) = arm_fallback(bot, ttr);
+                watchdog.arm(fallback, limit);
+            }
+            respond(message, bot)
+        })) {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(e)) => {
+                if log_level >= LogLevel::Error {
+                    writeln!(err, "Error: {}", e)?;
+                }
+                None
+            }
+            Err(payload) => {
+                if log_level >= LogLevel::Error {
+                    writeln!(err, "Error: panicked: {}", panic_message(&payload))?;
+                }
+                None
+            }
+        };
+        for e in bot.errors.borrow_mut().drain(..) {
+            if log_level >= LogLevel::Error {
+                writeln!(err, "Error: {}", e)?;
+            }
+        }
+
+        let output = match time_to_respond {
+            Some(_) => {
+                let late = reply.is_some();
+                match watchdog.finish(reply)? {
+                    Answer::Reply(output) => Some(output),
+                    Answer::Fallback(output) => {
+                        if log_level >= LogLevel::Error {
+                            let reason = if late { "move came too late" } else { "no move" };
+                            writeln!(err, "Error: {}, sent fallback {}", reason, output)?;
+                        }
+                        Some(output)
+                    }
+                }
+            }
+            None => {
+                if let Some(ref output) = reply {
+                    watchdog.send(output)?;
+                }
+                reply
+            }
+        };
+        if let Some(output) = output {
+            if log_level >= LogLevel::Debug {
+                writeln!(err, "Reply: {}", output)?;
+            }
+            if let Some(ref mut recorder) = recorder {
+                recorder.output(bot.settings.borrow().round, &output)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the fallback for a move and how long to wait before sending it.
+///
+/// Working these out touches the game state too, so a panic here passes
+/// at the answer limit rather than leaving the move unanswered.
+fn arm_fallback(bot: &BotState, time_to_respond: i32) -> (String, Duration) {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        (emergency_move(bot), timer::fallback_limit(&bot.settings.borrow(), time_to_respond))
+    })).unwrap_or_else(|_| (Move::new().to_string(), timer::answer_limit(time_to_respond)))
+}
+
+/// Get the message a panic was raised with.
+fn panic_message(payload: &Box<dyn Any + Send>) -> &str {
+    payload.downcast_ref::<&str>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
 
 /// Process incoming messages to update game state.
 pub fn handle_message(line: String, bot: &BotState) -> Result<Option<String>> {
-    let msg = line.parse::<Message>()?;
-    let reply = match msg {
+    respond(line.parse::<Message>()?, bot)
+}
+
+/// Update the game state with a parsed message and get the reply, if any.
+pub fn respond(message: Message, bot: &BotState) -> Result<Option<String>> {
+    let reply = match message {
         Message::Settings(Setting::TimeBank(n)) => {
             let mut settings = bot.settings.borrow_mut();
             settings.time_bank = n;
@@ -37,17 +163,19 @@ pub fn handle_message(line: String, bot: &BotState) -> Result<Option<String>> {
             let mut field = bot.field.borrow_mut();
             settings.id = id;
             field.player_id = id;
-            field.opponent_id = id + 1;
+            field.opponent_id = 1 - id;
             None
         }
         Message::Settings(Setting::FieldWidth(w)) => {
             let mut field = bot.field.borrow_mut();
             field.set_width(w as usize);
+            *bot.distances.borrow_mut() = None;
             None
         }
         Message::Settings(Setting::FieldHeight(h)) => {
             let mut field = bot.field.borrow_mut();
             field.set_height(h as usize);
+            *bot.distances.borrow_mut() = None;
             None
         }
         Message::Settings(Setting::MaxRounds(max)) => {
@@ -61,8 +189,19 @@ pub fn handle_message(line: String, bot: &BotState) -> Result<Option<String>> {
             None
         }
         Message::Update(Update::GameField(field_update)) => {
+            keep_unknown_cells(&field_update, bot)?;
             let mut field = bot.field.borrow_mut();
-            field.update_field(field_update);
+            field.update_field(field_update)?;
+
+            // The walls never move, so the distances only need working out once
+            let mut distances = bot.distances.borrow_mut();
+            let stale = match *distances {
+                Some(ref table) => !table.fits(&field),
+                None => true,
+            };
+            if stale {
+                *distances = Some(DistanceTable::new(&field));
+            }
             None
         }
         Message::Update(Update::PlayerSnippets(player, n)) => {
@@ -83,61 +222,127 @@ pub fn handle_message(line: String, bot: &BotState) -> Result<Option<String>> {
             }
             None
         }
-        Message::Action(Action::Character { time_to_respond: n }) => {
-            // TODO allow character choice configuration
-            Some("bixie".into())
+        Message::Unknown { kind, key, value } => {
+            if bot.strict {
+                let line = format!("{} {} {}", kind, key, value);
+                let command = format!("{} {}", kind, key);
+                return Err(Error::from(ParseErrorKind::UnknownCommand).in_line(&line, &command));
+            }
+            bot.unknown.borrow_mut().insert((kind, key), value);
+            None
+        }
+        Message::Action(Action::Character { .. }) => {
+            Some(bot.settings.borrow().character.to_string())
         }
-        Message::Action(Action::Move { time_to_respond: n }) => {
-            let ref field = bot.field.borrow();
-            let player_map = bot.players.borrow();
+        Message::Action(Action::Move { time_to_respond }) => {
+            let field = bot.field.borrow();
+            let distances = bot.distances.borrow();
+            let mut player_map = bot.players.borrow_mut();
             let settings = bot.settings.borrow();
-            let ref player_name = settings.name;
-            let ref player_id = settings.id;
-            
-            let mut action = String::new();
-
-            if let Some(player) = player_map.get(player_name) {
-                // TODO this is where decisions need to be made
-                action = make_move(field, player_id).to_string();
-
-                if let Some(detonation_time) = player.bomb_drop {
-                    // TODO maybe don't drop the bomb as soon as you get it
-                    action = format!("{};drop_bomb {}", action, detonation_time);
-                } 
-            } else {
+            let player_name = &settings.name;
+
+            if !player_map.contains_key(player_name) {
                 return Err(Error::PlayerNotFound(player_name.clone()))
             }
-            Some(action)
+            let (mut next_move, bomb) = {
+                let built;
+                let distances = match *distances {
+                    Some(ref table) => table,
+                    None => {
+                        built = DistanceTable::new(&field);
+                        &built
+                    }
+                };
+                let view = GameView {
+                    settings: &settings,
+                    players: &player_map,
+                    field: &field,
+                    distances,
+                };
+                let deadline = if bot.replaying {
+                    Deadline::new(::std::i32::MAX)
+                } else {
+                    Deadline::for_move(&settings, time_to_respond)
+                };
+                let next_move = bot.strategy.borrow_mut().choose_move(&view, &deadline);
+                let mut next_move = guard_move(&view, next_move);
+
+                // The strategy's own bomb needs a way out just like a planned one
+                let bomb = match next_move.bomb.take() {
+                    Some(ticks) => Some(check_escape(&view, next_move.move_type, ticks).map(|_| ticks)),
+                    None => plan_bomb(&view, next_move.move_type).map(|plan| Ok(plan.ticks)),
+                };
+                (next_move, bomb)
+            };
+
+            if let Some(player) = player_map.get_mut(player_name) {
+                // A bomb that can't be dropped is left off rather than losing the move
+                if let Some(bomb) = bomb {
+                    if let Err(e) = bomb.and_then(|ticks| next_move.drop_bomb(ticks, player)) {
+                        bot.errors.borrow_mut().push(e);
+                    }
+                }
+                player.bomb_drop = next_move.bomb;
+            }
+            Some(next_move.to_string())
         }
     };
     Ok(reply)
 }
 
-/// Decide the next move
-fn make_move(field: &Field, player_id: &i32) -> Move {
-    let mut next_move = Move::new();
-    let my_pos = &field.player_position;
-    let op_pos = &field.opponent_position;
-    let en_pos = &field.enemy_positions;
-    let bm_pos = &field.bomb_positions;
-    let sn_pos = &field.snippet_positions;
-
-    // TODO don't walk into walls
-    // TODO avoid enemies & ticking bombs
-    // TODO collect snippets and new bombs
-    
-    // Arbitrary position update
-    if let &Some(p) = my_pos {
-        if let &Some(o) = op_pos {
-            next_move.move_type = match  p.x % o.x {
-                0 => MoveType::Up,
-                1 => MoveType::Down,
-                2 => MoveType::Left,
-                3 => MoveType::Right,
-                _ => MoveType::Pass,
-            };
+/// Get the kind and key of everything in a message that this bot doesn't
+/// know yet, the way they are kept in `BotState::unknown`.
+fn unknown_keys(message: &Message) -> Vec<(String, String)> {
+    let mut keys = Vec::new();
+    match *message {
+        Message::Unknown { ref kind, ref key, .. } => keys.push((kind.clone(), key.clone())),
+        Message::Update(Update::GameField(ref cells)) => {
+            for item in cells.iter().flat_map(|cell| cell.items()) {
+                if let CellItem::Unknown(ref code) = *item {
+                    let kind_and_key = ("cell".to_string(), code.clone());
+                    if !keys.contains(&kind_and_key) {
+                        keys.push(kind_and_key);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    keys
+}
+
+/// Keep the cell codes we don't know yet with the last cell they were in,
+/// or fail on them in strict mode.
+fn keep_unknown_cells(cells: &[Cell], bot: &BotState) -> Result<()> {
+    for (index, cell) in cells.iter().enumerate() {
+        for item in cell.items() {
+            if let CellItem::Unknown(ref code) = *item {
+                if bot.strict {
+                    return Err(Error::from(ParseErrorKind::InvalidCellType).in_cell(index, code));
+                }
+                bot.unknown.borrow_mut().insert(("cell".to_string(), code.clone()), index.to_string());
+            }
         }
     }
+    Ok(())
+}
 
-    next_move
+/// Get a safe move to send when the strategy can't be relied on.
+pub fn emergency_move(bot: &BotState) -> String {
+    let field = bot.field.borrow();
+    let distances = bot.distances.borrow();
+    let players = bot.players.borrow();
+    let settings = bot.settings.borrow();
+    match *distances {
+        Some(ref distances) => {
+            let view = GameView {
+                settings: &settings,
+                players: &players,
+                field: &field,
+                distances,
+            };
+            fallback_move(&view).to_string()
+        }
+        None => Move::new().to_string(),
+    }
 }
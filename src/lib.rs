@@ -0,0 +1,27 @@
+//! The bot's parsing, game model and strategies
+//!
+//! The bot binary is a thin driver around `handler::handle_message`, and
+//! the arena and the integration tests build on the same modules.
+extern crate rand;
+
+pub mod blast;
+pub mod bomb;
+pub mod bot;
+pub mod config;
+pub mod distance;
+pub mod enemy;
+pub mod error;
+pub mod eval;
+pub mod field;
+pub mod handler;
+pub mod mcts;
+pub mod message;
+pub mod player;
+pub mod search;
+pub mod simple_matrix;
+pub mod simulator;
+pub mod strategy;
+pub mod timer;
+pub mod transcript;
+pub mod transport;
+pub mod watchdog;
@@ -1,26 +1,21 @@
-#[cfg(external)] extern crate futures;
-#[cfg(external)] extern crate tokio_core;
-#[cfg(external)] extern crate tokio_io;
-#[cfg(external)] extern crate tokio_file_unix;
- 
-#[cfg(external)] use futures::Stream;
-#[cfg(external)] use tokio_io::io;
-#[cfg(external)] use tokio_file_unix::{File, StdFile};
-use std::io::{self as stdio, BufRead, Write};
+extern crate rand;
+extern crate ruby_the_ms_hack_man_bot as hackman;
 
-mod bot;
-mod error;
-mod field;
-mod handler;
-mod message;
-mod player;
-mod simple_matrix;
+use std::env;
+use std::fs::File;
+use std::io::{self as stdio, BufReader, Write};
 
-use error::*;
-use bot::BotState;
-use handler::*;
+use hackman::error::*;
+use hackman::eval::Weights;
+use hackman::bot::BotState;
+use hackman::config::{self, Config, LogLevel};
+use hackman::handler;
+use hackman::strategy;
+use hackman::transcript::{read_transcript, recorded_options, replay, Recorder};
+use hackman::transport::Stdio;
+#[cfg(unix)]
+use hackman::transport::UnixSocket;
 
-#[cfg(not(test))]
 fn main() {
     let status = match start() {
         Ok(_) => 0,
@@ -33,67 +28,79 @@ fn main() {
 }
 
 /// Run the event loop
-#[cfg(not(test))]
 fn start() -> Result<()> {
-    let stdin = stdio::stdin();
-    let stdout = stdio::stdout();
-    let stderr = stdio::stderr();
-    let mut writer = stdout.lock();
-    let mut err = stderr.lock();
-    
-    // initialize the game state
-    let bot = BotState::new();
+    let config = Config::from_args(env::args().skip(1), |var| env::var(var).ok())?;
+    if config.help {
+        println!("{}", config::USAGE);
+        return Ok(());
+    }
+    if let Some(ref path) = config.replay {
+        return replay_transcript(path, &config);
+    }
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let bot = set_up_bot(&config, seed)?;
 
-    for line in stdin.lock().lines() {
-        if let Ok(line) = line {
-            match handle_message(line, &bot) {
-                Ok(output) => {
-                    match output {
-                        Some(o) => writeln!(writer, "{}", o)?,
-                        None => {},
-                    }
-                }
-                Err(e) => writeln!(err, "Error: {}", e)?,
-            }
+    let recorder = match config.record {
+        Some(ref path) => {
+            let mut recorder = Recorder::new(File::create(path)?);
+            recorder.header(&config.play_args(seed))?;
+            Some(recorder)
         }
+        None => None,
+    };
+    if config.log_level >= LogLevel::Info {
+        writeln!(stdio::stderr(), "Playing {} with the {} strategy, seed {}", config.character, config.strategy, seed)?;
+    }
+
+    match config.socket {
+        #[cfg(unix)]
+        Some(ref path) => handler::run(UnixSocket::accept(path)?, &bot, config.log_level, recorder)?,
+        #[cfg(not(unix))]
+        Some(_) => return Err(Error::InvalidArgument("--socket only works on Unix".into())),
+        None => handler::run(Stdio, &bot, config.log_level, recorder)?,
     }
     Err(Error::UnintentionalBreak)
 }
 
-// No external lib support.
-#[cfg(external)]
-#[cfg(not(test))]
-fn start() -> Result<()> {
-    // initialize the event loop
-    let mut core = tokio_core::reactor::Core::new()?;
-    let handle = core.handle();
-
-    // get the standard io as a file
-    let stdin = stdio::stdin();
-    let stdout = stdio::stdout();
-    let stderr = stdio::stderr();
-    let reader = File::new_nb(StdFile(stdin.lock()))?.into_reader(&handle)?;
-    let mut writer = File::new_nb(StdFile(stdout.lock()))?.into_io(&handle)?;
-    let mut err = File::new_nb(StdFile(stderr.lock()))?.into_io(&handle)?;
-
-    // initialize the game state
-    let bot = BotState::new();
+/// Initialize the game state with the configured strategy and character.
+fn set_up_bot(config: &Config, seed: usize) -> Result<BotState> {
+    let weights = match config.weights {
+        Some(ref path) => Weights::load(path)?,
+        None => Weights::builtin(),
+    };
+    let strategy = strategy::by_name(&config.strategy, seed, &weights)
+        .ok_or_else(|| Error::UnknownStrategy(config.strategy.clone()))?;
+    let bot = BotState::with_strategy(strategy);
+    bot.settings.borrow_mut().character = config.character;
+    Ok(bot)
+}
 
-    // turn it into a stream of lines and process them
-    let future = io::lines(reader).for_each(|line| {
-        match handle_message(line, &bot) {
-                Ok(output) => {
-                    match output {
-                        Some(o) => writeln!(writer, "{}", o),
-                        None => Ok(()),
-                    }
-                }
-                Err(e) => writeln!(err, "Error: {}", e),
-            }
-            .map(|_| ())
-    });
+/// Play a recorded transcript through a fresh bot and report differing replies.
+///
+/// The bot is set up the way the transcript's header says, or from `config`
+/// for a transcript without one, and its strategy does a fixed amount of
+/// work per move so that replays don't depend on the clock.
+fn replay_transcript(path: &str, config: &Config) -> Result<()> {
+    let entries = read_transcript(BufReader::new(File::open(path)?))?;
+    let recorded;
+    let config = match recorded_options(&entries) {
+        Some(options) => {
+            recorded = Config::from_args(options, |_| None)?;
+            &recorded
+        }
+        None => config,
+    };
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let bot = BotState { replaying: true, ..set_up_bot(config, seed)? };
+    bot.strategy.borrow_mut().fix_work();
 
-    // start the event loop
-    core.run(future)?;
-    Ok(())
+    let mismatches = replay(&entries, &bot);
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::ReplayMismatch(mismatches.len()))
+    }
 }
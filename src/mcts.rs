@@ -0,0 +1,235 @@
+//! Monte Carlo tree search for simultaneous moves
+//!
+//! Each node of the tree keeps separate move statistics for both players
+//! (decoupled UCT): both pick their move by UCB1 on their own statistics,
+//! the pair of moves leads to the child node, and random playouts from new
+//! nodes score the position. Our reward is the evaluation squashed into
+//! 0..1, and the opponent's reward is what is left of it.
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use distance::DistanceTable;
+use eval::{Evaluator, Weights};
+use field::Field;
+use player::{Move, MoveType};
+use simulator::{GameState, ME, OPPONENT};
+use strategy::{GameView, Greedy, Strategy};
+use timer::Deadline;
+
+/// How strongly UCB1 favours moves that have been tried less.
+const EXPLORATION: f64 = 1.4;
+/// Random rounds played out from each new node.
+const PLAYOUT_DEPTH: usize = 8;
+/// Evaluation difference that counts as a fairly sure win.
+const REWARD_SCALE: f64 = 400.0;
+
+#[derive(Clone, Default)]
+struct Arm {
+    visits: u32,
+    reward: f64,
+}
+
+struct Node {
+    state: GameState,
+    visits: u32,
+    moves: [Vec<MoveType>; 2],
+    arms: [Vec<Arm>; 2],
+    children: HashMap<(usize, usize), usize>,
+}
+
+/// Iterations per move when the work is fixed for a replay.
+pub const REPLAY_ITERATIONS: usize = 1000;
+
+/// Decoupled UCT search.
+pub struct Mcts {
+    rng: StdRng,
+    /// Stop after this many iterations even with time left.
+    pub max_iterations: Option<usize>,
+    /// The number of iterations run for the last move, for logging.
+    pub iterations: usize,
+    evaluator: Evaluator,
+}
+
+/// The tree and what it is built on.
+struct Tree<'a> {
+    field: &'a Field,
+    distances: &'a DistanceTable,
+    evaluator: &'a Evaluator,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(field: &'a Field,
+           distances: &'a DistanceTable,
+           evaluator: &'a Evaluator,
+           root: GameState)
+           -> Self {
+        let mut tree = Tree {
+            field,
+            distances,
+            evaluator,
+            nodes: Vec::new(),
+        };
+        tree.add_node(root);
+        tree
+    }
+
+    fn add_node(&mut self, state: GameState) -> usize {
+        let moves = [state.moves(self.field, ME), state.moves(self.field, OPPONENT)];
+        let arms = [vec![Arm::default(); moves[ME].len()],
+                    vec![Arm::default(); moves[OPPONENT].len()]];
+        self.nodes.push(Node {
+            state,
+            visits: 0,
+            moves,
+            arms,
+            children: HashMap::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Run one selection, expansion, playout and backup.
+    fn iterate(&mut self, rng: &mut StdRng) {
+        let mut path = Vec::new();
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if node.state.is_over() {
+                break;
+            }
+            let choice = (select(&node.arms[ME], node.visits), select(&node.arms[OPPONENT], node.visits));
+            path.push((current, choice));
+            match node.children.get(&choice).cloned() {
+                Some(child) => current = child,
+                None => {
+                    let next = node.state.step_walks(self.field,
+                                                     self.distances,
+                                                     node.moves[ME][choice.0],
+                                                     node.moves[OPPONENT][choice.1]);
+                    let child = self.add_node(next);
+                    self.nodes[current].children.insert(choice, child);
+                    current = child;
+                    break;
+                }
+            }
+        }
+
+        let reward = self.playout(current, rng);
+        self.nodes[current].visits += 1;
+        for (index, (mine, theirs)) in path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.arms[ME][mine].visits += 1;
+            node.arms[ME][mine].reward += reward;
+            node.arms[OPPONENT][theirs].visits += 1;
+            node.arms[OPPONENT][theirs].reward += 1.0 - reward;
+        }
+    }
+
+    /// Play random moves from a node and score where they end up.
+    fn playout(&self, from: usize, rng: &mut StdRng) -> f64 {
+        let mut state = self.nodes[from].state.clone();
+        for _ in 0..PLAYOUT_DEPTH {
+            if state.is_over() {
+                break;
+            }
+            let mine = state.moves(self.field, ME);
+            let theirs = state.moves(self.field, OPPONENT);
+            let mine = mine[rng.gen_range(0, mine.len())];
+            let theirs = theirs[rng.gen_range(0, theirs.len())];
+            state = state.step_walks(self.field, self.distances, mine, theirs);
+        }
+        let value = self.evaluator.evaluate(&state, self.field, self.distances) as f64;
+        0.5 + 0.5 * (value / REWARD_SCALE).tanh()
+    }
+}
+
+/// Pick an arm by UCB1, trying every arm once first.
+fn select(arms: &[Arm], visits: u32) -> usize {
+    if let Some(untried) = arms.iter().position(|a| a.visits == 0) {
+        return untried;
+    }
+    let log_visits = (visits.max(1) as f64).ln();
+    let ucb = |arm: &Arm| {
+        let n = arm.visits as f64;
+        arm.reward / n + EXPLORATION * (log_visits / n).sqrt()
+    };
+    (0..arms.len())
+        .max_by(|&a, &b| ucb(&arms[a]).partial_cmp(&ucb(&arms[b])).unwrap_or(::std::cmp::Ordering::Equal))
+        .unwrap_or(0)
+}
+
+impl Mcts {
+    pub fn new(seed: usize, weights: Weights) -> Self {
+        Mcts {
+            rng: SeedableRng::from_seed(&[seed][..]),
+            max_iterations: None,
+            iterations: 0,
+            evaluator: Evaluator::new(weights),
+        }
+    }
+}
+
+impl Strategy for Mcts {
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+
+    fn fix_work(&mut self) {
+        self.max_iterations = Some(self.max_iterations.unwrap_or(REPLAY_ITERATIONS));
+    }
+
+    fn choose_move(&mut self, view: &GameView, deadline: &Deadline) -> Move {
+        // Greedy answers when no iteration got as far as trying a move
+        let mut best = Greedy.choose_move(view, deadline);
+
+        let root = GameState::new(view.field, view.players, view.settings);
+        let mut tree = Tree::new(view.field, view.distances, &self.evaluator, root);
+        self.iterations = 0;
+        while !deadline.should_stop() && self.max_iterations.map_or(true, |max| self.iterations < max) {
+            tree.iterate(&mut self.rng);
+            self.iterations += 1;
+        }
+
+        let root = &tree.nodes[0];
+        let most_visited = (0..root.arms[ME].len())
+            .filter(|&i| root.arms[ME][i].visits > 0)
+            .max_by_key(|&i| root.arms[ME][i].visits);
+        if let Some(i) = most_visited {
+            best.move_type = root.moves[ME][i];
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eval::Weights;
+    use mcts::{Mcts, REPLAY_ITERATIONS};
+    use player::MoveType;
+    use strategy::{Strategy, TestGame};
+    use timer::Deadline;
+
+    #[test]
+    fn avoids_bug_test() {
+        // The snippet is closer on the right, but so is the bug
+        let mut game = TestGame::new(10, 1, "C,.,.,P0,.,C,E0,.,.,P1");
+        game.settings.max_rounds = 100;
+
+        let mut mcts = Mcts::new(1, Weights::builtin());
+        mcts.max_iterations = Some(2000);
+        let chosen = mcts.choose_move(&game.view(), &Deadline::new(60000)).move_type;
+        assert_eq!(chosen, MoveType::Left);
+        assert_eq!(mcts.iterations, 2000);
+
+        // Fixing the work for a replay keeps a limit already set
+        mcts.fix_work();
+        assert_eq!(mcts.max_iterations, Some(2000));
+        let mut fresh = Mcts::new(1, Weights::builtin());
+        fresh.fix_work();
+        fresh.choose_move(&game.view(), &Deadline::new(60000));
+        assert_eq!(fresh.iterations, REPLAY_ITERATIONS);
+    }
+}
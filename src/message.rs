@@ -1,14 +1,24 @@
+use std::fmt;
+
 use error::*;
-use field::{parse_field, Cell};
+use field::{format_field, parse_field, Cell};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Message {
     Settings(Setting),
     Update(Update),
     Action(Action),
+    /// A setting or update this bot doesn't know yet. `kind` is `settings`
+    /// or `update`, `key` is everything between it and the value, like
+    /// `player0 has_weapon`, and `value` is the rest of the line.
+    Unknown {
+        kind: String,
+        key: String,
+        value: String,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Setting {
     TimeBank(i32),
     TimePerMove(i32),
@@ -20,7 +30,7 @@ pub enum Setting {
     MaxRounds(i32),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Update {
     GameRound(i32),
     GameField(Vec<Cell>),
@@ -28,7 +38,7 @@ pub enum Update {
     PlayerBombs(String, i32),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Action {
     Character { time_to_respond: i32 },
     Move { time_to_respond: i32 },
@@ -37,27 +47,46 @@ pub enum Action {
 impl ::std::str::FromStr for Message {
     type Err = Error;
 
+    /// Parse a line from the engine, noting the line in any error.
     fn from_str(s: &str) -> Result<Self> {
-        use error::ParseErrorKind::{Incomplete, NumberFormat, UnknownCommand};
+        Message::parse(s).map_err(|e| e.in_line(s, &command_of(s)))
+    }
+}
+
+/// Get the words a line starts with that say what it is, like `update game field`.
+fn command_of(line: &str) -> String {
+    let words = if line.trim_start().starts_with("update") { 3 } else { 2 };
+    line.split_whitespace().take(words).collect::<Vec<_>>().join(" ")
+}
+
+/// Get the words of a line from the `n`th on, as the value of a line
+/// that isn't known.
+fn words_from(line: &str, n: usize) -> String {
+    line.split_whitespace().skip(n).collect::<Vec<_>>().join(" ")
+}
+
+impl Message {
+    fn parse(s: &str) -> Result<Self> {
+        use error::ParseErrorKind::{Incomplete, UnknownCommand};
         let mut words = s.split_whitespace();
-        let command = (words.next().ok_or(Error::ParseError(Incomplete))?,
-                       words.next().ok_or(Error::ParseError(Incomplete))?,
-                       words.next().ok_or(Error::ParseError(Incomplete))?,
+        let command = (words.next().ok_or_else(|| Error::from(Incomplete))?,
+                       words.next().ok_or_else(|| Error::from(Incomplete))?,
+                       words.next().ok_or_else(|| Error::from(Incomplete))?,
                        words.next());
 
         match command {
             ("settings", "timebank", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::TimeBank(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::TimeBank(n)))
+                    .map_err(Error::from)
             }
             ("settings", "time_per_move", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::TimePerMove(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::TimePerMove(n)))
+                    .map_err(Error::from)
             }
             ("settings", "player_names", value, None) => {
-                let names = value.split(",").map(|s| s.into()).collect();
+                let names = value.split(',').map(|s| s.into()).collect();
                 Ok(Message::Settings(Setting::PlayerNames(names)))
             }
             ("settings", "your_bot", value, None) => {
@@ -65,55 +94,216 @@ impl ::std::str::FromStr for Message {
             }
             ("settings", "your_botid", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::YourBotId(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::YourBotId(n)))
+                    .map_err(Error::from)
             }
             ("settings", "field_width", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::FieldWidth(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::FieldWidth(n)))
+                    .map_err(Error::from)
             }
             ("settings", "field_height", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::FieldHeight(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::FieldHeight(n)))
+                    .map_err(Error::from)
             }
             ("settings", "max_rounds", value, None) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Settings(Setting::MaxRounds(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Settings(Setting::MaxRounds(n)))
+                    .map_err(Error::from)
             }
             ("update", "game", "round", Some(value)) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Update(Update::GameRound(n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Update(Update::GameRound(n)))
+                    .map_err(Error::from)
             }
             ("update", "game", "field", Some(value)) => {
                 // Parse field into vector
                 parse_field(value)
-                    .and_then(|field_cells| Ok(Message::Update(Update::GameField(field_cells))))
+                    .map(|field_cells| Message::Update(Update::GameField(field_cells)))
             }
             ("update", player, "bombs", Some(value)) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Update(Update::PlayerBombs(player.into(), n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Update(Update::PlayerBombs(player.into(), n)))
+                    .map_err(Error::from)
             }
             ("update", player, "snippets", Some(value)) => {
                 value.parse::<i32>()
-                    .and_then(|n| Ok(Message::Update(Update::PlayerSnippets(player.into(), n))))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Update(Update::PlayerSnippets(player.into(), n)))
+                    .map_err(Error::from)
             }
             ("action", "character", time, None) => {
                 time.parse::<i32>()
-                    .and_then(|n| Ok(Message::Action(Action::Character { time_to_respond: n })))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Action(Action::Character { time_to_respond: n }))
+                    .map_err(Error::from)
             }
             ("action", "move", time, None) => {
                 time.parse::<i32>()
-                    .and_then(|n| Ok(Message::Action(Action::Move { time_to_respond: n })))
-                    .map_err(|e| Error::ParseError(NumberFormat(Box::new(e))))
+                    .map(|n| Message::Action(Action::Move { time_to_respond: n }))
+                    .map_err(Error::from)
+            }
+            ("settings", key, _, _) => {
+                Ok(Message::Unknown {
+                    kind: "settings".into(),
+                    key: key.into(),
+                    value: words_from(s, 2),
+                })
+            }
+            ("update", target, key, Some(_)) => {
+                Ok(Message::Unknown {
+                    kind: "update".into(),
+                    key: format!("{} {}", target, key),
+                    value: words_from(s, 3),
+                })
+            }
+            (_, _, _, _) => Err(Error::from(UnknownCommand)),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    /// Write the message the way the engine sends it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Settings(ref setting) => write!(f, "settings {}", setting),
+            Message::Update(ref update) => write!(f, "update {}", update),
+            Message::Action(ref action) => write!(f, "action {}", action),
+            Message::Unknown { ref kind, ref key, ref value } => write!(f, "{} {} {}", kind, key, value),
+        }
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Setting::TimeBank(n) => write!(f, "timebank {}", n),
+            Setting::TimePerMove(n) => write!(f, "time_per_move {}", n),
+            Setting::PlayerNames(ref names) => write!(f, "player_names {}", names.join(",")),
+            Setting::YourBot(ref name) => write!(f, "your_bot {}", name),
+            Setting::YourBotId(id) => write!(f, "your_botid {}", id),
+            Setting::FieldWidth(n) => write!(f, "field_width {}", n),
+            Setting::FieldHeight(n) => write!(f, "field_height {}", n),
+            Setting::MaxRounds(n) => write!(f, "max_rounds {}", n),
+        }
+    }
+}
+
+impl fmt::Display for Update {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Update::GameRound(n) => write!(f, "game round {}", n),
+            Update::GameField(ref cells) => write!(f, "game field {}", format_field(cells)),
+            Update::PlayerSnippets(ref player, n) => write!(f, "{} snippets {}", player, n),
+            Update::PlayerBombs(ref player, n) => write!(f, "{} bombs {}", player, n),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Character { time_to_respond } => write!(f, "character {}", time_to_respond),
+            Action::Move { time_to_respond } => write!(f, "move {}", time_to_respond),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error as StdError;
+
+    use rand::{Rng, SeedableRng, StdRng};
+
+    use error::{Error, ParseErrorKind};
+    use field::parse_field;
+    use message::{Action, Message, Setting, Update};
+
+    const CELL_CODES: [&str; 16] = [".", "x", "P0", "P1", "S", "S3", "Gl", "Gr", "E0", "E1", "E2",
+                                    "E3", "B", "B2", "C", "P1;E0"];
+
+    fn random_message(rng: &mut StdRng) -> Message {
+        let n = rng.gen_range(-5, 10000);
+        let name = format!("player{}", rng.gen_range(0, 3));
+        match rng.gen_range(0, 16) {
+            0 => Message::Settings(Setting::TimeBank(n)),
+            1 => Message::Settings(Setting::TimePerMove(n)),
+            2 => Message::Settings(Setting::PlayerNames(vec![name, "player9".into()])),
+            3 => Message::Settings(Setting::YourBot(name)),
+            4 => Message::Settings(Setting::YourBotId(n)),
+            5 => Message::Settings(Setting::FieldWidth(n)),
+            6 => Message::Settings(Setting::FieldHeight(n)),
+            7 => Message::Settings(Setting::MaxRounds(n)),
+            8 => Message::Update(Update::GameRound(n)),
+            9 => {
+                let cells: Vec<&str> = (0..rng.gen_range(1, 30))
+                    .map(|_| *rng.choose(&CELL_CODES).unwrap())
+                    .collect();
+                Message::Update(Update::GameField(parse_field(&cells.join(",")).unwrap()))
+            }
+            10 => Message::Update(Update::PlayerSnippets(name, n)),
+            11 => Message::Update(Update::PlayerBombs(name, n)),
+            12 => Message::Action(Action::Character { time_to_respond: n }),
+            13 => {
+                Message::Unknown {
+                    kind: "settings".into(),
+                    key: "gravity".into(),
+                    value: n.to_string(),
+                }
             }
-            (_, _, _, _) => Err(Error::ParseError(UnknownCommand)),
+            14 => {
+                Message::Unknown {
+                    kind: "update".into(),
+                    key: format!("{} has_weapon", name),
+                    value: "true".into(),
+                }
+            }
+            _ => Message::Action(Action::Move { time_to_respond: n }),
+        }
+    }
+
+    #[test]
+    fn message_round_trip_test() {
+        let mut rng: StdRng = SeedableRng::from_seed(&[42][..]);
+        for _ in 0..500 {
+            let message = random_message(&mut rng);
+            let text = message.to_string();
+            assert_eq!(text.parse::<Message>().unwrap(), message, "{}", text);
+            assert_eq!(text.parse::<Message>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn engine_text_test() {
+        for line in &["settings player_names player0,player1",
+                      "update game field .,x,P0;E1,Gl,B,B3,S,S2,C,W2",
+                      "update player1 snippets 2",
+                      "action move 10000",
+                      "update player0 is_paralyzed false",
+                      "settings weather light rain",
+                      "update player1 status stuck in a gate",
+                      "update game field \u{e9},."] {
+            assert_eq!(line.parse::<Message>().unwrap().to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn parse_error_context_test() {
+        let line = "update game field .,P0;Px,.";
+        match line.parse::<Message>() {
+            Err(Error::ParseError(e)) => {
+                assert!(matches!(e.kind, ParseErrorKind::NumberFormat(_)));
+                assert_eq!(e.line.as_deref(), Some(line));
+                assert_eq!(e.command.as_deref(), Some("update game field"));
+                assert_eq!(e.cell, Some((1, "Px".to_string())));
+            }
+            other => panic!("unexpected {:?}", other),
         }
+
+        let error = "settings timebank lots".parse::<Message>().unwrap_err();
+        assert!(error.to_string().contains("\"settings timebank\""));
+        assert!(error.source().is_some());
+        assert!("action".parse::<Message>().unwrap_err().source().is_none());
+        assert!("action dance 100".parse::<Message>().is_err());
+        assert!("update player0 has_weapon".parse::<Message>().is_err());
     }
 }
@@ -1,4 +1,7 @@
 use std::fmt;
+use std::str::FromStr;
+
+use error::{Error, ParseErrorKind, Result};
 
 #[derive(Debug)]
 pub struct Player {
@@ -12,7 +15,7 @@ pub struct Player {
 impl Player {
     pub fn new(name: String) -> Self {
         Player {
-            name: name,
+            name,
             bombs: 0,
             snippets: 0,
             character: CharacterType::Bixie,
@@ -21,28 +24,56 @@ impl Player {
     }
 
     pub fn character_type(&self) -> String {
-        match self.character {
-            CharacterType::Bixie => format!("bixie"),
-            CharacterType::Bixiette => format!("bixiette"),
-        }
+        self.character.to_string()
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CharacterType {
     Bixie,
     Bixiette,
 }
 
-impl CharacterType {}
+impl Default for CharacterType {
+    fn default() -> Self {
+        CharacterType::Bixie
+    }
+}
 
-#[derive(Debug)]
+impl fmt::Display for CharacterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CharacterType::Bixie => write!(f, "bixie"),
+            CharacterType::Bixiette => write!(f, "bixiette"),
+        }
+    }
+}
+
+impl FromStr for CharacterType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bixie" => Ok(CharacterType::Bixie),
+            "bixiette" => Ok(CharacterType::Bixiette),
+            _ => Err(Error::from(ParseErrorKind::UnknownCommand)),
+        }
+    }
+}
+
+/// The fuse lengths a bomb can be dropped with.
+pub const MIN_BOMB_TICKS: i32 = 2;
+pub const MAX_BOMB_TICKS: i32 = 5;
+
+/// A move, optionally dropping a bomb on the cell being left.
+#[derive(Debug, PartialEq)]
 pub struct Move {
     pub move_type: MoveType,
-    pub bomb_ticks: i32,
+    /// The fuse of the bomb to drop, if any.
+    pub bomb: Option<i32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MoveType {
     Up,
     Down,
@@ -51,18 +82,50 @@ pub enum MoveType {
     Pass,
 }
 
+impl MoveType {
+    /// The moves that change position.
+    pub const DIRECTIONS: [MoveType; 4] = [MoveType::Up, MoveType::Down, MoveType::Left, MoveType::Right];
+}
+
 impl Move {
     pub fn new() -> Self {
+        Move::walk(MoveType::Pass)
+    }
+
+    /// Move without dropping a bomb.
+    pub fn walk(move_type: MoveType) -> Self {
         Move {
-            move_type: MoveType::Pass,
-            bomb_ticks: 0,
+            move_type,
+            bomb: None,
         }
     }
+
+    /// Drop a bomb with the given fuse along with the move, as long as the
+    /// fuse is one the engine accepts and the player has a bomb to drop.
+    pub fn drop_bomb(&mut self, ticks: i32, player: &Player) -> Result<()> {
+        check_fuse(ticks)?;
+        if player.bombs <= 0 {
+            return Err(Error::InvalidBomb(format!("{} has no bombs", player.name)));
+        }
+        self.bomb = Some(ticks);
+        Ok(())
+    }
 }
 
-pub fn make_move() -> Move {
-    let mut next_move = Move::new();
-    next_move
+impl Default for Move {
+    fn default() -> Self {
+        Move::new()
+    }
+}
+
+/// Check that the engine accepts a fuse of `ticks` rounds.
+pub fn check_fuse(ticks: i32) -> Result<()> {
+    if (MIN_BOMB_TICKS..=MAX_BOMB_TICKS).contains(&ticks) {
+        Ok(())
+    } else {
+        let range = format!("{}..{}", MIN_BOMB_TICKS, MAX_BOMB_TICKS);
+        Err(Error::InvalidBomb(format!("fuse {} outside {}", ticks, range)))
+    }
 }
 
 impl fmt::Display for Move {
@@ -74,6 +137,69 @@ impl fmt::Display for Move {
             MoveType::Right => "right",
             MoveType::Pass => "pass",
         };
-        write!(f, "{};drop_bomb {}", move_type, self.bomb_ticks)
+        match self.bomb {
+            Some(ticks) => write!(f, "{};drop_bomb {}", move_type, ticks),
+            None => write!(f, "{}", move_type),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = Error;
+
+    /// Parse a reply in the form the engine accepts, like `up;drop_bomb 3`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().split(';');
+        let move_type = match parts.next().map(str::trim) {
+            Some("up") => MoveType::Up,
+            Some("down") => MoveType::Down,
+            Some("left") => MoveType::Left,
+            Some("right") => MoveType::Right,
+            Some("pass") => MoveType::Pass,
+            _ => return Err(Error::from(ParseErrorKind::UnknownCommand)),
+        };
+        let bomb = match parts.next().map(|p| p.split_whitespace().collect::<Vec<_>>()) {
+            Some(ref words) if words.len() == 2 && words[0] == "drop_bomb" => {
+                let ticks = words[1].parse::<i32>()
+                    .map_err(Error::from)?;
+                check_fuse(ticks)?;
+                Some(ticks)
+            }
+            Some(_) => return Err(Error::from(ParseErrorKind::UnknownCommand)),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(Error::from(ParseErrorKind::UnknownCommand));
+        }
+        Ok(Move { move_type, bomb })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use player::{Move, MoveType, Player};
+
+    #[test]
+    fn move_output_test() {
+        assert_eq!(Move::walk(MoveType::Up).to_string(), "up");
+
+        let mut player = Player::new("me".into());
+        let mut next_move = Move::walk(MoveType::Left);
+        assert!(next_move.drop_bomb(3, &player).is_err());
+        player.bombs = 1;
+        assert!(next_move.drop_bomb(1, &player).is_err());
+        assert!(next_move.drop_bomb(6, &player).is_err());
+        next_move.drop_bomb(5, &player).unwrap();
+        assert_eq!(next_move.to_string(), "left;drop_bomb 5");
+    }
+
+    #[test]
+    fn move_parse_test() {
+        assert_eq!("pass".parse::<Move>().unwrap(), Move::walk(MoveType::Pass));
+        assert_eq!("down;drop_bomb 2".parse::<Move>().unwrap(),
+                   Move { move_type: MoveType::Down, bomb: Some(2) });
+        assert!("down;drop_bomb 0".parse::<Move>().is_err());
+        assert!("down;drop_bomb 2;drop_bomb 2".parse::<Move>().is_err());
+        assert!("jump".parse::<Move>().is_err());
     }
 }
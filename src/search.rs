@@ -0,0 +1,164 @@
+//! Look ahead over the simulator with iterative deepening
+//!
+//! Every ply tries each of our moves against each of the opponent's, with
+//! the bugs moving as `enemy` predicts, and assumes the opponent answers
+//! with whatever is worst for us. The search goes one ply deeper at a time
+//! and keeps the best move of the deepest search that finished before the
+//! deadline.
+use distance::DistanceTable;
+use eval::{Evaluator, Weights};
+use field::Field;
+use player::{Move, MoveType};
+use simulator::{GameState, ME, OPPONENT};
+use strategy::{GameView, Greedy, Strategy};
+use timer::Deadline;
+
+/// Never search deeper than this many rounds.
+pub const MAX_DEPTH: usize = 8;
+
+/// How deep every move searches when the work is fixed for a replay.
+pub const REPLAY_DEPTH: usize = 4;
+
+/// Iterative deepening lookahead.
+#[derive(Default)]
+pub struct Search {
+    /// Stop at this depth even with time left.
+    pub max_depth: Option<usize>,
+    /// The depth of the last search that finished, for logging.
+    pub depth_reached: usize,
+    evaluator: Evaluator,
+}
+
+/// What every node of the search needs to look at.
+struct Context<'a> {
+    field: &'a Field,
+    distances: &'a DistanceTable,
+    deadline: &'a Deadline,
+    evaluator: &'a Evaluator,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search::default()
+    }
+
+    pub fn with_weights(weights: Weights) -> Self {
+        Search {
+            max_depth: None,
+            depth_reached: 0,
+            evaluator: Evaluator::new(weights),
+        }
+    }
+
+    /// Get the value of our best move at `state`, or `None` when time runs out.
+    fn value(ctx: &Context, state: &GameState, depth: usize) -> Option<i32> {
+        if ctx.deadline.should_stop() {
+            return None;
+        }
+        if depth == 0 || state.is_over() {
+            return Some(ctx.evaluator.evaluate(state, ctx.field, ctx.distances));
+        }
+        Search::best_reply(ctx, state, depth).map(|(_, value)| value)
+    }
+
+    /// Get our best move at `state` with its value, assuming the opponent
+    /// answers it as badly for us as it can.
+    fn best_reply(ctx: &Context, state: &GameState, depth: usize) -> Option<(MoveType, i32)> {
+        let mut best: Option<(MoveType, i32)> = None;
+        for mine in state.moves(ctx.field, ME) {
+            let mut worst = ::std::i32::MAX;
+            for theirs in state.moves(ctx.field, OPPONENT) {
+                let next = state.step_walks(ctx.field, ctx.distances, mine, theirs);
+                worst = worst.min(Search::value(ctx, &next, depth - 1)?);
+
+                // The opponent already has an answer that makes this no better
+                if best.map_or(false, |(_, b)| worst <= b) {
+                    break;
+                }
+            }
+            if best.map_or(true, |(_, b)| worst > b) {
+                best = Some((mine, worst));
+            }
+        }
+        best
+    }
+}
+
+impl Strategy for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn fix_work(&mut self) {
+        self.max_depth = Some(self.max_depth.unwrap_or(REPLAY_DEPTH));
+    }
+
+    fn choose_move(&mut self, view: &GameView, deadline: &Deadline) -> Move {
+        // Start from the greedy move in case not even one ply fits in the time
+        let mut best = Greedy.choose_move(view, deadline);
+        self.depth_reached = 0;
+
+        let root = GameState::new(view.field, view.players, view.settings);
+        let ctx = Context {
+            field: view.field,
+            distances: view.distances,
+            deadline,
+            evaluator: &self.evaluator,
+        };
+        let rounds_left = (root.max_rounds - root.round).max(1) as usize;
+
+        let max_depth = self.max_depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+
+        for depth in 1..max_depth.min(rounds_left) + 1 {
+            match Search::best_reply(&ctx, &root, depth) {
+                Some((move_type, _)) => {
+                    best.move_type = move_type;
+                    self.depth_reached = depth;
+                }
+                None => break,
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use player::MoveType;
+    use search::{Search, REPLAY_DEPTH};
+    use strategy::{Strategy, TestGame};
+    use timer::Deadline;
+
+    fn choose(cells: &str, width: usize, millis: i32) -> (MoveType, usize) {
+        let mut game = TestGame::new(width, 1, cells);
+        game.settings.max_rounds = 100;
+        let mut search = Search::new();
+        let move_type = search.choose_move(&game.view(), &Deadline::new(millis)).move_type;
+        (move_type, search.depth_reached)
+    }
+
+    #[test]
+    fn avoids_bug_test() {
+        // The snippet is closer on the right, but so is the bug
+        let (move_type, depth) = choose("C,.,.,P0,.,C,E0,.,.,P1", 10, 2000);
+        assert_eq!(move_type, MoveType::Left);
+        assert!(depth >= 2);
+    }
+
+    #[test]
+    fn fixed_work_test() {
+        let mut game = TestGame::new(10, 1, "C,.,.,P0,.,C,E0,.,.,P1");
+        game.settings.max_rounds = 100;
+        let mut search = Search::new();
+        search.fix_work();
+        search.choose_move(&game.view(), &Deadline::new(60000));
+        assert_eq!(search.depth_reached, REPLAY_DEPTH);
+    }
+
+    #[test]
+    fn out_of_time_test() {
+        let (move_type, depth) = choose("C,.,P0,.,.,P1", 6, 0);
+        assert_eq!(move_type, MoveType::Left);
+        assert_eq!(depth, 0);
+    }
+}
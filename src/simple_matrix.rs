@@ -1,7 +1,7 @@
 //! A simple matrix to index into a vector
 use std::ops::{Index, IndexMut};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Matrix<T> {
     pub m: Vec<T>,
     pub width: usize,
@@ -12,8 +12,19 @@ impl<T> Matrix<T> {
     pub fn new(width: usize, height: usize) -> Self {
         Matrix {
             m: Vec::new(),
-            width: width,
-            height: height,
+            width,
+            height,
+        }
+    }
+
+    /// Create a matrix with every element set to `value`.
+    pub fn filled(width: usize, height: usize, value: T) -> Self
+        where T: Clone
+    {
+        Matrix {
+            m: vec![value; width * height],
+            width,
+            height,
         }
     }
 
@@ -27,7 +38,26 @@ impl<T> Matrix<T> {
 
     /// Get the coordinates for the given index.
     pub fn position(&self, index: usize) -> (usize, usize) {
-        (index % self.width, index / self.height)
+        (index % self.width, index / self.width)
+    }
+
+    /// Get the index for the given coordinates.
+    pub fn index_of(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// Check that the coordinates fall inside the matrix.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Get the element at the coordinates, if they are inside the matrix.
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if self.contains(x, y) {
+            self.m.get(self.index_of(x, y))
+        } else {
+            None
+        }
     }
 }
 
@@ -35,18 +65,19 @@ impl<T> Index<(usize, usize)> for Matrix<T> {
     type Output = T;
 
     fn index(&self, i: (usize, usize)) -> &T {
-        &self.m[i.0 * self.width + i.1]
+        &self.m[self.index_of(i.0, i.1)]
     }
 }
 
 impl<T> IndexMut<(usize, usize)> for Matrix<T> {
     fn index_mut(&mut self, i: (usize, usize)) -> &mut T {
-        &mut self.m[i.0 * self.width + i.1]
+        let index = self.index_of(i.0, i.1);
+        &mut self.m[index]
     }
 }
 
 #[cfg(test)]
-mod Test {
+mod test {
     use simple_matrix::Matrix;
 
     #[test]
@@ -70,4 +101,21 @@ mod Test {
         assert_eq!(matrix[(1, 2)], matrix[matrix.position(7)]);
         assert_eq!(matrix[(2, 2)], matrix[matrix.position(8)]);
     }
+
+    #[test]
+    fn non_square_position_test() {
+        let matrix = Matrix {
+            m: vec![
+                0, 1, 2, 3,
+                4, 5, 6, 7],
+            width: 4,
+            height: 2,
+        };
+
+        assert_eq!(matrix.position(5), (1, 1));
+        assert_eq!(matrix[(3, 0)], 3);
+        assert_eq!(matrix[(1, 1)], 5);
+        assert_eq!(matrix.get(4, 0), None);
+        assert_eq!(matrix.get(3, 1), Some(&7));
+    }
 }
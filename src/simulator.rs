@@ -0,0 +1,358 @@
+//! Deterministic forward model of a Ms. Hackman round
+//!
+//! The simulator only tracks what changes during a game. Walls and gates
+//! come from the `Field` passed to `GameState::step`, and bug movement comes
+//! from the models in `enemy`. Snippets that the engine drops in at random
+//! are not simulated.
+use std::collections::HashMap;
+
+use blast::BlastTimeline;
+use bot::Settings;
+use distance::DistanceTable;
+use enemy::BugPredictor;
+use field::{AiType, Cell, CellItem, Enemy, Field, Point, TickingBomb};
+use player::{Move, MoveType, Player};
+
+/// Index of our bot in `GameState::players`.
+pub const ME: usize = 0;
+/// Index of the opponent in `GameState::players`.
+pub const OPPONENT: usize = 1;
+
+/// Snippets lost when hit by a bug or caught in an explosion.
+pub const SNIPPET_PENALTY: i32 = 4;
+
+/// The order spawn points hand out bug types in.
+const SPAWN_CYCLE: [AiType; 4] = [AiType::Chase, AiType::Predict, AiType::Lever, AiType::FarChase];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimPlayer {
+    pub id: i32,
+    pub point: Option<Point>,
+    pub snippets: i32,
+    pub bombs: i32,
+    pub alive: bool,
+}
+
+/// A bug spawn point and the rounds left until it releases a bug.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub point: Point,
+    pub countdown: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameState {
+    pub round: i32,
+    pub max_rounds: i32,
+    pub players: [SimPlayer; 2],
+    pub enemies: Vec<Enemy>,
+    pub snippets: Vec<Point>,
+    pub bombs: Vec<Point>,
+    pub ticking_bombs: Vec<TickingBomb>,
+    pub spawns: Vec<Spawn>,
+    pub bugs_spawned: usize,
+}
+
+impl SimPlayer {
+    fn new(id: i32, point: Option<Point>, player: Option<&Player>) -> Self {
+        SimPlayer {
+            id,
+            point,
+            snippets: player.map_or(0, |p| p.snippets),
+            bombs: player.map_or(0, |p| p.bombs),
+            alive: true,
+        }
+    }
+
+    /// Take a snippet penalty, dying when there are not enough to pay it.
+    fn hit(&mut self) {
+        self.snippets -= SNIPPET_PENALTY;
+        if self.snippets < 0 {
+            self.alive = false;
+        }
+    }
+}
+
+impl GameState {
+    /// Gather the game state from what the bot knows.
+    pub fn new(field: &Field, players: &HashMap<String, Player>, settings: &Settings) -> Self {
+        let me = players.get(&settings.name);
+        let opponent = players.values().find(|p| p.name != settings.name);
+        let spawns = field.field.m
+            .iter()
+            .enumerate()
+            .flat_map(|(i, cell)| {
+                let (x, y) = field.field.position(i);
+                cell.items()
+                    .iter()
+                    .filter_map(move |item| match *item {
+                        CellItem::Spawn(countdown) => Some(Spawn { point: Point::new(x, y), countdown }),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        GameState {
+            round: settings.round,
+            max_rounds: settings.max_rounds,
+            players: [SimPlayer::new(field.player_id, field.player_position, me),
+                      SimPlayer::new(field.opponent_id, field.opponent_position, opponent)],
+            enemies: field.enemies.clone(),
+            snippets: field.snippet_positions.clone(),
+            bombs: field.bomb_positions.clone(),
+            ticking_bombs: field.ticking_bombs.clone(),
+            spawns,
+            bugs_spawned: 0,
+        }
+    }
+
+    /// Check whether either player has died or the rounds have run out.
+    pub fn is_over(&self) -> bool {
+        self.players.iter().any(|p| !p.alive) || self.round >= self.max_rounds
+    }
+
+    /// Get the moves worth trying for `player`, which is only waiting once
+    /// it is out of the game.
+    pub fn moves(&self, field: &Field, player: usize) -> Vec<MoveType> {
+        match self.players[player].point {
+            Some(point) if self.players[player].alive => field.legal_moves(point),
+            _ => vec![MoveType::Pass],
+        }
+    }
+
+    /// Get the state after a round in which both players only walk.
+    pub fn step_walks(&self, field: &Field, distances: &DistanceTable, mine: MoveType, theirs: MoveType) -> GameState {
+        let mut next = self.clone();
+        next.step(field, distances, [&Move::walk(mine), &Move::walk(theirs)]);
+        next
+    }
+
+    /// Advance the game by one round with both players' moves.
+    ///
+    /// `moves` is indexed like `players`. Illegal moves leave the player
+    /// where it is, and bomb drops without a bomb in hand are ignored.
+    pub fn step(&mut self, field: &Field, distances: &DistanceTable, moves: [&Move; 2]) {
+        let before: Vec<Option<Point>> = self.players.iter().map(|p| p.point).collect();
+
+        // Drop bombs where the players stand, then walk
+        for (player, mv) in self.players.iter_mut().zip(moves.iter()) {
+            let point = match player.point {
+                Some(p) if player.alive => p,
+                _ => continue,
+            };
+            if let Some(ticks) = mv.bomb.filter(|_| player.bombs > 0) {
+                player.bombs -= 1;
+                self.ticking_bombs.push(TickingBomb { point, ticks });
+            }
+            player.point = Some(field.step(point, mv.move_type).unwrap_or(point));
+        }
+
+        self.pick_up();
+
+        // Bugs walk towards the players' new positions
+        let targets: Vec<Point> = self.players.iter()
+            .filter(|p| p.alive)
+            .filter_map(|p| p.point)
+            .collect();
+        let predictor = BugPredictor::new(field, distances);
+        let enemies_before: Vec<Point> = self.enemies.iter().map(|e| e.point).collect();
+        for enemy in &mut self.enemies {
+            enemy.point = predictor.next_step(enemy, &targets);
+        }
+
+        // Bugs that meet or pass through a player bite and disappear
+        let mut bitten = vec![false; self.enemies.len()];
+        for (player, start) in self.players.iter_mut().zip(before) {
+            let point = match player.point {
+                Some(p) if player.alive => p,
+                _ => continue,
+            };
+            for (i, enemy) in self.enemies.iter().enumerate() {
+                let swapped = start == Some(enemy.point) && enemies_before[i] == point;
+                if !bitten[i] && (enemy.point == point || swapped) {
+                    bitten[i] = true;
+                    player.hit();
+                }
+            }
+        }
+        let mut bitten = bitten.into_iter();
+        self.enemies.retain(|_| !bitten.next().unwrap_or(false));
+
+        self.detonate(field);
+        self.spawn_bugs();
+        self.round += 1;
+    }
+
+    /// Lay the state back out as field cells, the way the engine sends them.
+    pub fn to_cells(&self, field: &Field) -> Vec<Cell> {
+        let mut cells: Vec<Vec<CellItem>> = field.field.m
+            .iter()
+            .map(|cell| {
+                cell.items()
+                    .iter()
+                    .filter(|item| matches!(**item, CellItem::Inaccessible | CellItem::Gate(_)))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+
+        {
+            let mut put = |p: Point, item: CellItem| cells[field.field.index_of(p.x, p.y)].push(item);
+            for spawn in &self.spawns {
+                put(spawn.point, CellItem::Spawn(spawn.countdown));
+            }
+            for player in self.players.iter().filter(|p| p.alive) {
+                if let Some(point) = player.point {
+                    put(point, CellItem::Player(player.id));
+                }
+            }
+            for enemy in &self.enemies {
+                put(enemy.point, CellItem::Enemy(enemy.ai));
+            }
+            for &bomb in &self.bombs {
+                put(bomb, CellItem::Bomb(-1));
+            }
+            for bomb in &self.ticking_bombs {
+                put(bomb.point, CellItem::Bomb(bomb.ticks));
+            }
+            for &snippet in &self.snippets {
+                put(snippet, CellItem::CodeSnippet);
+            }
+        }
+
+        cells.into_iter()
+            .map(|items| {
+                if items.is_empty() {
+                    Some(CellItem::Empty).into_iter().collect()
+                } else {
+                    items.into_iter().collect()
+                }
+            })
+            .collect()
+    }
+
+    /// Collect snippets and bombs under the players.
+    fn pick_up(&mut self) {
+        for player in self.players.iter_mut().filter(|p| p.alive) {
+            let point = match player.point {
+                Some(p) => p,
+                None => continue,
+            };
+            if self.snippets.contains(&point) {
+                player.snippets += 1;
+            }
+            if self.bombs.contains(&point) {
+                player.bombs += 1;
+            }
+        }
+        let players = &self.players;
+        let taken = |p: &Point| players.iter().any(|player| player.alive && player.point == Some(*p));
+        self.snippets.retain(|p| !taken(p));
+        self.bombs.retain(|p| !taken(p));
+    }
+
+    /// Tick the fuses and blow up the bombs that run out.
+    fn detonate(&mut self, field: &Field) {
+        let timeline = BlastTimeline::new(field, &self.ticking_bombs, 1);
+        let hit = timeline.hits_on(1);
+
+        for player in self.players.iter_mut().filter(|p| p.alive) {
+            if player.point.map_or(false, |p| hit.contains(&p)) {
+                player.hit();
+            }
+        }
+        self.enemies.retain(|e| !hit.contains(&e.point));
+
+        self.ticking_bombs = timeline.detonations()
+            .iter()
+            .filter(|&&(_, round)| round > 1)
+            .map(|&(bomb, _)| TickingBomb { ticks: bomb.ticks - 1, ..bomb })
+            .collect();
+    }
+
+    /// Count down the spawn points and release bugs from the ones that are ready.
+    fn spawn_bugs(&mut self) {
+        for spawn in &mut self.spawns {
+            spawn.countdown = match spawn.countdown {
+                Some(n) if n <= 1 => {
+                    let ai = SPAWN_CYCLE[self.bugs_spawned % SPAWN_CYCLE.len()];
+                    self.enemies.push(Enemy { point: spawn.point, ai });
+                    self.bugs_spawned += 1;
+                    None
+                }
+                Some(n) => Some(n - 1),
+                None => None,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use distance::DistanceTable;
+    use field::{Field, Point};
+    use player::{Move, MoveType, Player};
+    use simulator::{GameState, ME, OPPONENT};
+    use strategy::TestGame;
+
+    fn game_from(width: usize, height: usize, cells: &str) -> (Field, DistanceTable, GameState) {
+        let mut game = TestGame::new(width, height, cells);
+        game.settings.name = "me".into();
+        game.settings.max_rounds = 10;
+        for name in &["me", "them"] {
+            game.players.insert(name.to_string(), Player::new(name.to_string()));
+        }
+        let state = GameState::new(&game.field, &game.players, &game.settings);
+        (game.field, game.distances, state)
+    }
+
+    #[test]
+    fn pickups_and_gates_test() {
+        let (field, table, mut state) = game_from(5, 1, "Gl,P0,C,B,Gr;P1");
+        let moves = [&Move::walk(MoveType::Right), &Move::walk(MoveType::Right)];
+        state.step(&field, &table, moves);
+
+        assert_eq!(state.players[ME].point, Some(Point::new(2, 0)));
+        assert_eq!(state.players[ME].snippets, 1);
+        assert_eq!(state.players[OPPONENT].point, Some(Point::new(0, 0)));
+        assert!(state.snippets.is_empty());
+        assert_eq!(state.round, 1);
+    }
+
+    #[test]
+    fn bombs_and_bugs_test() {
+        let (field, table, mut state) = game_from(6, 1, "P0,B,.,.,E0,P1");
+        state.players[ME].snippets = 4;
+        state.players[OPPONENT].snippets = 5;
+
+        // We pick up the bomb while the bug bites the opponent and disappears
+        state.step(&field, &table, [&Move::walk(MoveType::Right), &Move::walk(MoveType::Pass)]);
+        assert_eq!(state.players[ME].bombs, 1);
+        assert!(state.enemies.is_empty());
+        assert_eq!(state.players[OPPONENT].snippets, 1);
+
+        // Drop the bomb with a two round fuse and step away
+        let drop = Move { move_type: MoveType::Left, bomb: Some(2) };
+        state.step(&field, &table, [&drop, &Move::walk(MoveType::Pass)]);
+        assert_eq!(state.ticking_bombs.len(), 1);
+        assert_eq!(state.players[ME].bombs, 0);
+        assert!(!state.is_over());
+
+        // The blast runs the length of the row and catches both players
+        state.step(&field, &table, [&Move::walk(MoveType::Pass), &Move::walk(MoveType::Pass)]);
+        assert!(state.ticking_bombs.is_empty());
+        assert_eq!(state.players[ME].snippets, 0);
+        assert!(state.players[ME].alive);
+        assert!(!state.players[OPPONENT].alive);
+        assert!(state.is_over());
+    }
+
+    #[test]
+    fn spawn_test() {
+        let (field, table, mut state) = game_from(3, 1, "P0,S1,P1");
+        state.step(&field, &table, [&Move::walk(MoveType::Pass), &Move::walk(MoveType::Pass)]);
+
+        assert_eq!(state.enemies.len(), 1);
+        assert_eq!(state.spawns[0].countdown, None);
+    }
+}
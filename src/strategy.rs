@@ -0,0 +1,290 @@
+//! Ways of picking the next move
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use blast::BlastTimeline;
+use bot::Settings;
+use distance::DistanceTable;
+use enemy::BugPredictor;
+use eval::Weights;
+use field::{Field, Point};
+use mcts::Mcts;
+use player::{Move, MoveType, Player};
+use search::Search;
+use timer::Deadline;
+
+/// The names `by_name` understands, the default first.
+pub const STRATEGY_NAMES: [&str; 5] = ["greedy", "search", "mcts", "wander", "idle"];
+
+/// A read-only view of what the bot knows when it is asked to move.
+pub struct GameView<'a> {
+    pub settings: &'a Settings,
+    pub players: &'a HashMap<String, Player>,
+    pub field: &'a Field,
+    pub distances: &'a DistanceTable,
+}
+
+impl<'a> GameView<'a> {
+    /// Get our own player.
+    pub fn me(&self) -> Option<&Player> {
+        self.players.get(&self.settings.name)
+    }
+
+    /// Get the other player.
+    pub fn opponent(&self) -> Option<&Player> {
+        self.players.values().find(|p| p.name != self.settings.name)
+    }
+}
+
+/// Everything a `GameView` borrows, for tests to set up and look at.
+#[cfg(test)]
+pub struct TestGame {
+    pub settings: Settings,
+    pub players: HashMap<String, Player>,
+    pub field: Field,
+    pub distances: DistanceTable,
+}
+
+#[cfg(test)]
+impl TestGame {
+    /// Start a game on a field built from cell strings, with default
+    /// settings and no players.
+    pub fn new(width: usize, height: usize, cells: &str) -> Self {
+        let field = ::field::field_from(width, height, cells);
+        TestGame {
+            settings: Settings::default(),
+            players: HashMap::new(),
+            distances: DistanceTable::new(&field),
+            field,
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> GameView<'a> {
+        GameView {
+            settings: &self.settings,
+            players: &self.players,
+            field: &self.field,
+            distances: &self.distances,
+        }
+    }
+}
+
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    /// Pick the next move, finishing before the deadline.
+    fn choose_move(&mut self, view: &GameView, deadline: &Deadline) -> Move;
+
+    /// Stop every move after a fixed amount of work rather than at the
+    /// deadline, so that a replayed game gets the same moves each time.
+    /// Strategies that don't search have nothing to change.
+    fn fix_work(&mut self) {}
+}
+
+/// Get the strategy with the given name, seeding any randomness it uses and
+/// scoring positions with `weights`.
+pub fn by_name(name: &str, seed: usize, weights: &Weights) -> Option<Box<dyn Strategy>> {
+    match name {
+        "greedy" => Some(Box::new(Greedy)),
+        "search" => Some(Box::new(Search::with_weights(weights.clone()))),
+        "mcts" => Some(Box::new(Mcts::new(seed, weights.clone()))),
+        "wander" => Some(Box::new(Wander::new(seed))),
+        "idle" => Some(Box::new(Idle)),
+        _ => None,
+    }
+}
+
+/// Walk to the closest snippet, stepping aside from bugs and explosions.
+pub struct Greedy;
+
+/// Walk in a random direction that isn't a wall.
+pub struct Wander {
+    rng: StdRng,
+}
+
+/// Stand still.
+pub struct Idle;
+
+impl Strategy for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn choose_move(&mut self, view: &GameView, _deadline: &Deadline) -> Move {
+        let field = view.field;
+        let distances = view.distances;
+        let mut next_move = Move::new();
+        let my_pos = match field.player_position {
+            Some(p) => p,
+            None => return next_move,
+        };
+
+        // TODO collect new bombs
+
+        // Head for the closest snippet
+        if let Some((target, _)) = distances.nearest(my_pos, &field.snippet_positions) {
+            if let Some(move_type) = distances.first_move(field, my_pos, target) {
+                next_move.move_type = move_type;
+            }
+        }
+
+        // Step aside when the way to it isn't safe
+        let safety = Safety::new(field, distances, my_pos);
+        let next_pos = field.step(my_pos, next_move.move_type).unwrap_or(my_pos);
+        if !safety.is_safe(next_pos) {
+            next_move.move_type = safety.safest_move();
+        }
+
+        next_move
+    }
+}
+
+/// How risky the cells next to us are this round.
+struct Safety<'a> {
+    field: &'a Field,
+    distances: &'a DistanceTable,
+    from: Point,
+    enemy_cells: Vec<Point>,
+    blasts: BlastTimeline,
+}
+
+impl<'a> Safety<'a> {
+    fn new(field: &'a Field, distances: &'a DistanceTable, from: Point) -> Self {
+        // Bugs count where they are and where they are about to be
+        let mut players = vec![from];
+        players.extend(field.opponent_position);
+        let predictor = BugPredictor::new(field, distances);
+        let enemy_cells = field.enemies
+            .iter()
+            .flat_map(|enemy| vec![enemy.point, predictor.next_step(enemy, &players)])
+            .collect();
+
+        Safety {
+            field,
+            distances,
+            from,
+            enemy_cells,
+            blasts: BlastTimeline::from_field(field, 1),
+        }
+    }
+
+    /// Get how far the closest bug is from a point.
+    fn danger(&self, p: Point) -> usize {
+        self.enemy_cells
+            .iter()
+            .filter_map(|&enemy| self.distances.distance(enemy, p))
+            .min()
+            .unwrap_or(::std::usize::MAX)
+    }
+
+    /// Check that a point is clear of bugs and of bombs about to go off.
+    fn is_safe(&self, p: Point) -> bool {
+        self.danger(p) > 1 && !self.blasts.is_hit(p, 1)
+    }
+
+    /// Get the legal move that keeps furthest from trouble.
+    fn safest_move(&self) -> MoveType {
+        // Ties go to the last move, so put standing still there
+        let mut moves = self.field.legal_moves(self.from);
+        moves.rotate_left(1);
+        moves.into_iter()
+            .map(|move_type| (move_type, self.field.step(self.from, move_type).unwrap_or(self.from)))
+            .max_by_key(|&(_, p)| (!self.blasts.is_hit(p, 1), self.danger(p)))
+            .map_or(MoveType::Pass, |(move_type, _)| move_type)
+    }
+}
+
+/// Get the safest legal move without looking any further ahead, for when
+/// a strategy fails to answer.
+pub fn fallback_move(view: &GameView) -> Move {
+    let mut next_move = Move::new();
+    if let Some(my_pos) = view.field.player_position {
+        next_move.move_type = Safety::new(view.field, view.distances, my_pos).safest_move();
+    }
+    next_move
+}
+
+/// Replace a move the engine would refuse with the safest legal one.
+///
+/// An illegal move wastes the round, so every strategy's move goes through
+/// here before it is sent.
+pub fn guard_move(view: &GameView, mut next_move: Move) -> Move {
+    let field = view.field;
+    if let Some(my_pos) = field.player_position {
+        if !field.legal_moves(my_pos).contains(&next_move.move_type) {
+            next_move.move_type = Safety::new(field, view.distances, my_pos).safest_move();
+        }
+    }
+    next_move
+}
+
+impl Wander {
+    pub fn new(seed: usize) -> Self {
+        Wander { rng: SeedableRng::from_seed(&[seed][..]) }
+    }
+}
+
+impl Strategy for Wander {
+    fn name(&self) -> &'static str {
+        "wander"
+    }
+
+    fn choose_move(&mut self, view: &GameView, _deadline: &Deadline) -> Move {
+        let mut next_move = Move::new();
+        if let Some(p) = view.field.player_position {
+            let moves = view.field.moves_from(p);
+            if !moves.is_empty() {
+                next_move.move_type = moves[self.rng.gen_range(0, moves.len())].0;
+            }
+        }
+        next_move
+    }
+}
+
+impl Strategy for Idle {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+
+    fn choose_move(&mut self, _view: &GameView, _deadline: &Deadline) -> Move {
+        Move::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eval::Weights;
+    use player::MoveType;
+    use player::Move;
+    use strategy::{by_name, guard_move, TestGame, STRATEGY_NAMES};
+    use timer::Deadline;
+
+    #[test]
+    fn strategies_test() {
+        let game = TestGame::new(5, 1, "C,.,P0,.,E0");
+        let view = game.view();
+        let weights = Weights::builtin();
+
+        for name in STRATEGY_NAMES.iter() {
+            assert_eq!(by_name(name, 0, &weights).unwrap().name(), *name);
+        }
+        assert_eq!(by_name("greedy", 0, &weights).unwrap().choose_move(&view, &Deadline::new(1000)).move_type,
+                   MoveType::Left);
+        assert_eq!(by_name("idle", 0, &weights).unwrap().choose_move(&view, &Deadline::new(1000)).move_type,
+                   MoveType::Pass);
+        assert!(by_name("unknown", 0, &weights).is_none());
+    }
+
+    #[test]
+    fn guard_move_test() {
+        // Up is off the field and left is a wall, so only right or waiting
+        // are allowed, and waiting keeps clear of the bug
+        let game = TestGame::new(4, 1, "x,P0,.,E0");
+        let view = game.view();
+
+        assert_eq!(guard_move(&view, Move::walk(MoveType::Up)).move_type, MoveType::Pass);
+        assert_eq!(guard_move(&view, Move::walk(MoveType::Left)).move_type, MoveType::Pass);
+        assert_eq!(guard_move(&view, Move::walk(MoveType::Right)).move_type, MoveType::Right);
+    }
+}
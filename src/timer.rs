@@ -0,0 +1,115 @@
+//! Work out how long a move may take and keep track of it
+//!
+//! The engine adds `time_per_move` to the time bank every round and passes
+//! what is left of the bank as `time_to_respond`. Each move gets its
+//! `time_per_move` plus a fair share of whatever has been saved up, spread
+//! over the rounds still to play, less a safety margin for the time it
+//! takes to write the reply.
+use std::time::{Duration, Instant};
+
+use bot::Settings;
+
+/// Milliseconds held back from every move.
+pub const SAFETY_MARGIN: i32 = 30;
+
+/// Get the number of milliseconds the current move may use.
+pub fn move_budget(settings: &Settings, time_to_respond: i32) -> i32 {
+    let rounds_left = (settings.max_rounds - settings.round).max(1);
+    let per_move = settings.time_per_move.max(0).min(time_to_respond);
+    let saved = (time_to_respond - per_move).max(0);
+    let budget = per_move + saved / rounds_left;
+    (budget.min(time_to_respond) - SAFETY_MARGIN).max(0)
+}
+
+/// Milliseconds a move may run past its budget before the fallback goes out.
+pub const GRACE_PERIOD: i32 = 50;
+
+/// Get the time after which an answer would come too late, less the
+/// safety margin.
+pub fn answer_limit(time_to_respond: i32) -> Duration {
+    Duration::from_millis(time_to_respond.saturating_sub(SAFETY_MARGIN).max(0) as u64)
+}
+
+/// Get the time after which the fallback goes out instead of the move:
+/// the move's budget and a grace period, but never past the answer limit.
+pub fn fallback_limit(settings: &Settings, time_to_respond: i32) -> Duration {
+    let budget = move_budget(settings, time_to_respond) + GRACE_PERIOD;
+    Duration::from_millis(budget as u64).min(answer_limit(time_to_respond))
+}
+
+/// The point in time a move has to be finished by.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    start: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Start a deadline `millis` milliseconds from now.
+    pub fn new(millis: i32) -> Self {
+        Deadline {
+            start: Instant::now(),
+            budget: Duration::from_millis(millis.max(0) as u64),
+        }
+    }
+
+    /// Start the deadline for a move.
+    pub fn for_move(settings: &Settings, time_to_respond: i32) -> Self {
+        Deadline::new(move_budget(settings, time_to_respond))
+    }
+
+    /// Check whether the time is up.
+    pub fn should_stop(&self) -> bool {
+        self.start.elapsed() >= self.budget
+    }
+
+    /// Get the time left before the deadline.
+    pub fn remaining(&self) -> Duration {
+        self.budget.checked_sub(self.start.elapsed()).unwrap_or_default()
+    }
+
+    /// Get the total time given to the move.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bot::Settings;
+    use timer::{fallback_limit, move_budget, Deadline, GRACE_PERIOD, SAFETY_MARGIN};
+
+    #[test]
+    fn move_budget_test() {
+        let settings = Settings {
+            time_per_move: 500,
+            round: 90,
+            max_rounds: 100,
+            ..Settings::default()
+        };
+
+        // A full bank is spread over the last ten rounds
+        assert_eq!(move_budget(&settings, 10500), 500 + 1000 - SAFETY_MARGIN);
+        // An empty bank leaves only what the engine hands out each move
+        assert_eq!(move_budget(&settings, 200), 200 - SAFETY_MARGIN);
+        assert_eq!(move_budget(&settings, 10), 0);
+
+        // The fallback waits a little past the budget, but not past the bank
+        let limit = (500 + 1000 - SAFETY_MARGIN + GRACE_PERIOD) as u64;
+        assert_eq!(fallback_limit(&settings, 10500), Duration::from_millis(limit));
+        assert_eq!(fallback_limit(&settings, 200), Duration::from_millis((200 - SAFETY_MARGIN) as u64));
+
+        let last_round = Settings { round: 100, ..settings };
+        assert_eq!(move_budget(&last_round, 2000), 2000 - SAFETY_MARGIN);
+    }
+
+    #[test]
+    fn deadline_test() {
+        assert!(Deadline::new(0).should_stop());
+        let deadline = Deadline::new(60000);
+        assert!(!deadline.should_stop());
+        assert!(deadline.remaining() <= deadline.budget());
+    }
+}
@@ -0,0 +1,211 @@
+//! Record the lines exchanged with the engine and play them back
+//!
+//! A transcript has one entry per line, with tab separated fields:
+//! direction (`<` from the engine, `>` from the bot), round number,
+//! milliseconds and the line itself. Input entries are timed from the
+//! start of the recording, output entries by how long the reply took.
+//!
+//! It starts with a header entry, direction `#`, holding the strategy,
+//! character, seed and weights the bot played with as command line options.
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::time::Instant;
+
+use bot::BotState;
+use error::*;
+use handler::handle_message;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Header,
+    Input,
+    Output,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub direction: Direction,
+    pub round: i32,
+    pub millis: u64,
+    pub line: String,
+}
+
+/// A reply that came out differently on replay.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub round: i32,
+    pub input: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// Writes transcript entries as the game goes.
+pub struct Recorder<W: Write> {
+    writer: W,
+    start: Instant,
+    last_input: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        let now = Instant::now();
+        Recorder { writer, start: now, last_input: now }
+    }
+
+    /// Note the options the game is played with, before anything else.
+    pub fn header(&mut self, options: &str) -> Result<()> {
+        self.write(Direction::Header, 0, 0, options)
+    }
+
+    pub fn input(&mut self, round: i32, line: &str) -> Result<()> {
+        self.last_input = Instant::now();
+        let millis = millis_between(self.start, self.last_input);
+        self.write(Direction::Input, round, millis, line)
+    }
+
+    pub fn output(&mut self, round: i32, line: &str) -> Result<()> {
+        let millis = millis_between(self.last_input, Instant::now());
+        self.write(Direction::Output, round, millis, line)
+    }
+
+    fn write(&mut self, direction: Direction, round: i32, millis: u64, line: &str) -> Result<()> {
+        let entry = Entry { direction, round, millis, line: line.into() };
+        writeln!(self.writer, "{}", entry)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn millis_between(from: Instant, to: Instant) -> u64 {
+    let elapsed = to.duration_since(from);
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+/// Read every entry of a transcript.
+pub fn read_transcript<R: BufRead>(reader: R) -> Result<Vec<Entry>> {
+    reader.lines()
+        .filter(|line| line.as_ref().map(|l| !l.is_empty()).unwrap_or(true))
+        .map(|line| line?.parse::<Entry>())
+        .collect()
+}
+
+/// Get the options a transcript was recorded with, if it has a header.
+pub fn recorded_options(entries: &[Entry]) -> Option<Vec<String>> {
+    entries.first()
+        .filter(|entry| entry.direction == Direction::Header)
+        .map(|entry| entry.line.split_whitespace().map(String::from).collect())
+}
+
+/// Feed the inputs of a transcript to a fresh bot and compare its replies.
+pub fn replay(entries: &[Entry], bot: &BotState) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut entries = entries.iter().peekable();
+
+    while let Some(entry) = entries.next() {
+        if entry.direction != Direction::Input {
+            continue;
+        }
+        let expected = match entries.peek() {
+            Some(next) if next.direction == Direction::Output => Some(next.line.clone()),
+            _ => None,
+        };
+        let actual = handle_message(entry.line.clone(), bot).unwrap_or(None);
+        if actual != expected {
+            mismatches.push(Mismatch {
+                round: entry.round,
+                input: entry.line.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+    mismatches
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Header => "#",
+            Direction::Input => "<",
+            Direction::Output => ">",
+        };
+        write!(f, "{}\t{}\t{}\t{}", direction, self.round, self.millis, self.line)
+    }
+}
+
+impl FromStr for Entry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        use error::ParseErrorKind::{Incomplete, UnknownCommand};
+        let mut fields = s.splitn(4, '\t');
+        let mut next = || fields.next().ok_or_else(|| Error::from(Incomplete));
+        let direction = match next()? {
+            "#" => Direction::Header,
+            "<" => Direction::Input,
+            ">" => Direction::Output,
+            _ => return Err(Error::from(UnknownCommand)),
+        };
+        let round = next()?
+            .parse::<i32>()
+            .map_err(Error::from)?;
+        let millis = next()?
+            .parse::<u64>()
+            .map_err(Error::from)?;
+        let line = next()?.to_string();
+        Ok(Entry { direction, round, millis, line })
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "round {}: {:?} expected {:?}, got {:?}",
+               self.round,
+               self.input,
+               self.expected,
+               self.actual)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bot::BotState;
+    use transcript::{read_transcript, recorded_options, replay, Direction, Entry, Recorder};
+
+    #[test]
+    fn record_and_read_test() {
+        let mut buffer = Vec::new();
+        {
+            let mut recorder = Recorder::new(&mut buffer);
+            recorder.header("--strategy mcts --seed 7").unwrap();
+            recorder.input(0, "action character 10000").unwrap();
+            recorder.output(0, "bixie").unwrap();
+        }
+        let entries = read_transcript(&buffer[..]).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].direction, Direction::Input);
+        assert_eq!(entries[2].line, "bixie");
+        assert_eq!(entries[2].to_string().parse::<Entry>().unwrap(), entries[2]);
+        assert_eq!(recorded_options(&entries).unwrap(), ["--strategy", "mcts", "--seed", "7"]);
+        assert_eq!(recorded_options(&entries[1..]), None);
+    }
+
+    #[test]
+    fn replay_test() {
+        let transcript = "\
+<\t0\t0\tsettings your_bot player0
+<\t0\t1\taction character 10000
+>\t0\t0\tbixiette
+<\t0\t2\tsettings time_per_move 500
+";
+        let entries = read_transcript(transcript.as_bytes()).unwrap();
+        let mismatches = replay(&entries, &BotState::new());
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].expected, Some("bixiette".into()));
+        assert_eq!(mismatches[0].actual, Some("bixie".into()));
+    }
+}
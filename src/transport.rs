@@ -0,0 +1,170 @@
+//! Where the bot's messages come from and its replies go
+//!
+//! A transport splits into a source of engine lines, a sink for replies and
+//! a sink for diagnostics. The reply sink is handed to the watchdog thread,
+//! so it has to be `Send`.
+use std::io::{self, BufRead, BufReader, Cursor, Stderr, Stdin, Stdout, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+
+pub trait Transport {
+    type Lines: BufRead;
+    type Replies: Write + Send + 'static;
+    type Diagnostics: Write;
+
+    /// Take the transport apart into its lines, replies and diagnostics.
+    fn split(self) -> (Self::Lines, Self::Replies, Self::Diagnostics);
+}
+
+/// The standard streams, the way the competition engine talks to bots.
+pub struct Stdio;
+
+impl Transport for Stdio {
+    type Lines = BufReader<Stdin>;
+    type Replies = Stdout;
+    type Diagnostics = Stderr;
+
+    fn split(self) -> (Self::Lines, Self::Replies, Self::Diagnostics) {
+        (BufReader::new(io::stdin()), io::stdout(), io::stderr())
+    }
+}
+
+/// A buffer that can be written from one place and read from another.
+#[derive(Clone, Debug, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Get everything written so far.
+    pub fn contents(&self) -> String {
+        let bytes = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Lines given up front, with the output kept for looking at afterwards.
+pub struct Memory {
+    input: String,
+    replies: SharedBuffer,
+    diagnostics: SharedBuffer,
+}
+
+impl Memory {
+    pub fn new(input: &str) -> Self {
+        Memory {
+            input: input.to_string(),
+            replies: SharedBuffer::default(),
+            diagnostics: SharedBuffer::default(),
+        }
+    }
+
+    /// Get a handle on the replies written once the transport is in use.
+    pub fn replies(&self) -> SharedBuffer {
+        self.replies.clone()
+    }
+
+    /// Get a handle on the diagnostics written once the transport is in use.
+    pub fn diagnostics(&self) -> SharedBuffer {
+        self.diagnostics.clone()
+    }
+}
+
+impl Transport for Memory {
+    type Lines = Cursor<Vec<u8>>;
+    type Replies = SharedBuffer;
+    type Diagnostics = SharedBuffer;
+
+    fn split(self) -> (Self::Lines, Self::Replies, Self::Diagnostics) {
+        (Cursor::new(self.input.into_bytes()), self.replies, self.diagnostics)
+    }
+}
+
+/// A Unix domain socket carrying the engine's lines and the replies, with
+/// diagnostics still going to stderr.
+#[cfg(unix)]
+pub struct UnixSocket {
+    reader: UnixStream,
+    writer: UnixStream,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Connect to an engine listening at `path`.
+    pub fn connect(path: &str) -> io::Result<Self> {
+        UnixSocket::new(UnixStream::connect(path)?)
+    }
+
+    /// Listen at `path` and wait for an engine or debugger to connect.
+    pub fn accept(path: &str) -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        let (stream, _) = listener.accept()?;
+        UnixSocket::new(stream)
+    }
+
+    /// Use a connected stream, duplicating it for the replies.
+    fn new(stream: UnixStream) -> io::Result<Self> {
+        Ok(UnixSocket {
+            writer: stream.try_clone()?,
+            reader: stream,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixSocket {
+    type Lines = BufReader<UnixStream>;
+    type Replies = UnixStream;
+    type Diagnostics = Stderr;
+
+    fn split(self) -> (Self::Lines, Self::Replies, Self::Diagnostics) {
+        (BufReader::new(self.reader), self.writer, io::stderr())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    use transport::{Memory, Transport, UnixSocket};
+
+    #[test]
+    fn memory_test() {
+        let memory = Memory::new("one\ntwo\n");
+        let replies = memory.replies();
+        let (lines, mut writer, _) = memory.split();
+
+        let lines: Vec<String> = lines.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["one", "two"]);
+        writeln!(writer, "three").unwrap();
+        assert_eq!(replies.contents(), "three\n");
+    }
+
+    #[test]
+    fn unix_socket_test() {
+        let (engine, bot) = UnixStream::pair().unwrap();
+        let (mut lines, mut replies, _) = UnixSocket::new(bot).unwrap().split();
+
+        let mut engine_reader = BufReader::new(engine.try_clone().unwrap());
+        let mut engine = engine;
+        writeln!(engine, "action character 100").unwrap();
+        let mut line = String::new();
+        lines.read_line(&mut line).unwrap();
+        assert_eq!(line, "action character 100\n");
+
+        writeln!(replies, "bixie").unwrap();
+        line.clear();
+        engine_reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "bixie\n");
+    }
+}
@@ -0,0 +1,168 @@
+//! Make sure every move request gets an answer in time
+//!
+//! The bot state lives on the main thread, so the move itself can't be
+//! worked out anywhere else. Instead a fallback move is armed before the
+//! strategy runs, and a watchdog thread sends it if the real reply hasn't
+//! gone out by the deadline. Every reply goes through the watchdog's
+//! writer, so a late reply is dropped rather than sent as a second answer.
+use std::io::{self, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Where the move request being worked on stands.
+enum Slot {
+    Idle,
+    Armed { fallback: String, deadline: Instant },
+    Fired(String),
+    Shutdown,
+}
+
+struct Shared<W> {
+    writer: Mutex<W>,
+    slot: Mutex<Slot>,
+    wake: Condvar,
+}
+
+/// The line that answered a move request.
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    /// The strategy's reply went out in time.
+    Reply(String),
+    /// The fallback went out instead.
+    Fallback(String),
+}
+
+pub struct Watchdog<W: Write + Send + 'static> {
+    shared: Arc<Shared<W>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<W: Write + Send + 'static> Watchdog<W> {
+    /// Start the watchdog thread, writing replies to `writer`.
+    pub fn new(writer: W) -> Self {
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(writer),
+            slot: Mutex::new(Slot::Idle),
+            wake: Condvar::new(),
+        });
+        let watched = shared.clone();
+        let thread = thread::spawn(move || watch(&watched));
+        Watchdog {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Send `fallback` after `budget` unless the move is finished first.
+    pub fn arm(&self, fallback: String, budget: Duration) {
+        let mut slot = lock(&self.shared.slot);
+        *slot = Slot::Armed {
+            fallback,
+            deadline: Instant::now() + budget,
+        };
+        self.shared.wake.notify_all();
+    }
+
+    /// Answer the armed move with `reply`, or with the fallback when there
+    /// is no reply, unless the watchdog already sent the fallback.
+    pub fn finish(&self, reply: Option<String>) -> io::Result<Answer> {
+        let mut slot = lock(&self.shared.slot);
+        let answer = match (::std::mem::replace(&mut *slot, Slot::Idle), reply) {
+            (Slot::Fired(fallback), _) => return Ok(Answer::Fallback(fallback)),
+            (_, Some(reply)) => Answer::Reply(reply),
+            (Slot::Armed { fallback, .. }, None) => Answer::Fallback(fallback),
+            (_, None) => Answer::Fallback("pass".into()),
+        };
+        match answer {
+            Answer::Reply(ref line) | Answer::Fallback(ref line) => self.send(line)?,
+        }
+        Ok(answer)
+    }
+
+    /// Write a line that isn't a move, like the character choice.
+    pub fn send(&self, line: &str) -> io::Result<()> {
+        let mut writer = lock(&self.shared.writer);
+        writeln!(writer, "{}", line)?;
+        writer.flush()
+    }
+}
+
+impl<W: Write + Send + 'static> Drop for Watchdog<W> {
+    fn drop(&mut self) {
+        *lock(&self.shared.slot) = Slot::Shutdown;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Wait for armed moves and send their fallback once the deadline passes.
+fn watch<W: Write>(shared: &Shared<W>) {
+    let mut slot = lock(&shared.slot);
+    loop {
+        let wait = match *slot {
+            Slot::Shutdown => return,
+            Slot::Armed { deadline, .. } => deadline.checked_duration_since(Instant::now()),
+            _ => {
+                slot = shared.wake.wait(slot).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+        };
+        match wait {
+            Some(wait) if wait > Duration::from_millis(0) => {
+                slot = shared.wake.wait_timeout(slot, wait).unwrap_or_else(|e| e.into_inner()).0;
+            }
+            _ => {
+                if let Slot::Armed { fallback, .. } = ::std::mem::replace(&mut *slot, Slot::Idle) {
+                    let mut writer = lock(&shared.writer);
+                    writeln!(writer, "{}", fallback).and_then(|_| writer.flush()).ok();
+                    *slot = Slot::Fired(fallback);
+                }
+            }
+        }
+    }
+}
+
+/// Lock a mutex, carrying on after a panic elsewhere poisoned it.
+fn lock<'a, T>(mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use transport::SharedBuffer;
+    use watchdog::{Answer, Watchdog};
+
+    #[test]
+    fn reply_in_time_test() {
+        let output = SharedBuffer::default();
+        let watchdog = Watchdog::new(output.clone());
+        watchdog.send("bixie").unwrap();
+        watchdog.arm("pass".into(), Duration::from_secs(60));
+        assert_eq!(watchdog.finish(Some("up".into())).unwrap(), Answer::Reply("up".into()));
+        drop(watchdog);
+        assert_eq!(output.contents(), "bixie\nup\n");
+    }
+
+    #[test]
+    fn late_reply_test() {
+        let output = SharedBuffer::default();
+        let watchdog = Watchdog::new(output.clone());
+        watchdog.arm("left".into(), Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(output.contents(), "left\n");
+
+        // The late reply is dropped
+        assert_eq!(watchdog.finish(Some("up".into())).unwrap(), Answer::Fallback("left".into()));
+
+        // A move that failed outright gets its fallback straight away
+        watchdog.arm("right".into(), Duration::from_secs(60));
+        assert_eq!(watchdog.finish(None).unwrap(), Answer::Fallback("right".into()));
+        assert_eq!(output.contents(), "left\nright\n");
+    }
+}
@@ -0,0 +1,167 @@
+//! Play scripted engine messages through the library the way the bot does
+extern crate ruby_the_ms_hack_man_bot as hackman;
+
+use std::fs::File;
+
+use hackman::bot::BotState;
+use hackman::config::LogLevel;
+use hackman::eval::Weights;
+use hackman::field::Point;
+use hackman::handler::{handle_message, run};
+use hackman::message::Message;
+use hackman::player::{Move, MoveType};
+use hackman::strategy::{self, GameView, Strategy};
+use hackman::timer::Deadline;
+use hackman::transcript::{read_transcript, replay, Recorder};
+use hackman::transport::Memory;
+
+const SETUP: [&str; 9] = ["settings timebank 10000",
+                          "settings time_per_move 500",
+                          "settings player_names player0,player1",
+                          "settings your_bot player0",
+                          "settings your_botid 0",
+                          "settings field_width 5",
+                          "settings field_height 1",
+                          "settings max_rounds 100",
+                          "update game round 1"];
+
+fn send(bot: &BotState, line: &str) -> Option<String> {
+    handle_message(line.to_string(), bot).unwrap()
+}
+
+fn set_up(field: &str) -> BotState {
+    let bot = BotState::new();
+    for line in SETUP.iter() {
+        send(&bot, line);
+    }
+    send(&bot, &format!("update game field {}", field));
+    bot
+}
+
+#[test]
+fn character_test() {
+    let bot = set_up(".,P0,.,.,P1");
+    assert_eq!(send(&bot, "action character 10000"), Some("bixie".to_string()));
+}
+
+#[test]
+fn move_to_snippet_test() {
+    let bot = set_up("C,P0,.,.,P1");
+    assert_eq!(bot.field.borrow().player_position, Some(Point::new(1, 0)));
+
+    let reply = send(&bot, "action move 10000").unwrap();
+    let parsed: Move = reply.parse().unwrap();
+    assert_eq!(parsed, Move::walk(MoveType::Left));
+}
+
+#[test]
+fn updates_test() {
+    let bot = set_up(".,P0,.,.,P1");
+    assert_eq!(send(&bot, "update player1 snippets 3"), None);
+    assert_eq!(send(&bot, "update player0 bombs 2"), None);
+    assert_eq!(bot.players.borrow()["player1"].snippets, 3);
+    assert_eq!(bot.players.borrow()["player0"].bombs, 2);
+
+    assert!(handle_message("update nobody bombs 1".to_string(), &bot).is_err());
+    assert_eq!(send(&bot, "settings your_botid 1"), None);
+    assert_eq!(bot.field.borrow().opponent_id, 0);
+    assert!("action dance 100".parse::<Message>().is_err());
+}
+
+#[test]
+fn run_test() {
+    let mut input = SETUP.join("\n");
+    input.push_str("\nupdate game field C,P0,.,.,P1\naction character 10000\nbogus line\naction move 10000\n");
+    let memory = Memory::new(&input);
+    let (replies, diagnostics) = (memory.replies(), memory.diagnostics());
+
+    let bot = BotState::new();
+    run(memory, &bot, LogLevel::Error, None::<Recorder<File>>).unwrap();
+    assert_eq!(replies.contents(), "set time_bank 10000\nbixie\nleft\n");
+    assert!(diagnostics.contents().starts_with("Error: "));
+}
+
+#[test]
+fn odd_input_test() {
+    // Nothing the engine sends can stop the bot from answering later moves
+    let mut input = SETUP.join("\n");
+    input.push_str("\nupdate game field .,\u{e9},.,.,P1\nupdate game field C,P0,.,.,P1\naction move 10000\n");
+    let memory = Memory::new(&input);
+    let replies = memory.replies();
+
+    let bot = BotState::new();
+    run(memory, &bot, LogLevel::Error, None::<Recorder<File>>).unwrap();
+    assert_eq!(replies.contents(), "set time_bank 10000\nleft\n");
+}
+
+/// Always walks left and drops a bomb on the way.
+struct Bomber;
+
+impl Strategy for Bomber {
+    fn name(&self) -> &'static str {
+        "bomber"
+    }
+
+    fn choose_move(&mut self, _: &GameView, _: &Deadline) -> Move {
+        Move { move_type: MoveType::Left, bomb: Some(2) }
+    }
+}
+
+#[test]
+fn unsafe_bomb_test() {
+    // Walking left into the corner leaves no way out of our own blast
+    let mut input = SETUP.join("\n");
+    input.push_str("\nupdate game field .,P0,.,.,P1\nupdate player0 bombs 1\naction move 10000\n");
+    let memory = Memory::new(&input);
+    let (replies, diagnostics) = (memory.replies(), memory.diagnostics());
+
+    let bot = BotState::with_strategy(Box::new(Bomber));
+    run(memory, &bot, LogLevel::Error, None::<Recorder<File>>).unwrap();
+    assert_eq!(replies.contents(), "set time_bank 10000\nleft\n");
+    assert!(diagnostics.contents().contains("no way out of a blast with fuse 2"));
+}
+
+#[test]
+fn unknown_test() {
+    let bot = set_up(".,P0,.,.,P1");
+    assert_eq!(send(&bot, "update player0 has_weapon true"), None);
+    assert_eq!(send(&bot, "settings gravity 2"), None);
+    assert_eq!(send(&bot, "update player0 has_weapon false"), None);
+    let unknown = bot.unknown.borrow();
+    assert_eq!(unknown[&("update".to_string(), "player0 has_weapon".to_string())], "false");
+    assert_eq!(unknown[&("settings".to_string(), "gravity".to_string())], "2");
+    drop(unknown);
+
+    // A new cell code still lets the rest of the field through
+    assert_eq!(send(&bot, "update game field .,W3,P0;W3,.,P1"), None);
+    assert_eq!(bot.field.borrow().player_position, Some(Point::new(2, 0)));
+    assert_eq!(bot.unknown.borrow()[&("cell".to_string(), "W3".to_string())], "2");
+
+    // Strict mode still fails on anything new
+    let strict = BotState { strict: true, ..BotState::new() };
+    let error = handle_message("update player0 is_paralyzed true".to_string(), &strict).unwrap_err();
+    assert!(error.to_string().contains("update player0 is_paralyzed"));
+    let error = handle_message("update game field .,W3,P0,.,P1".to_string(), &strict).unwrap_err();
+    assert!(error.to_string().contains("cell 1 \"W3\""));
+    assert!(strict.unknown.borrow().is_empty());
+}
+
+#[test]
+fn replay_test() {
+    // With the work fixed, a search replays the same moves however little
+    // time the transcript gave it
+    let mut transcript = String::new();
+    for line in SETUP.iter().chain(&["update game field C,P0,.,E0,P1", "action move 1"]) {
+        transcript.push_str(&format!("<\t0\t0\t{}\n", line));
+    }
+    let entries = read_transcript(transcript.as_bytes()).unwrap();
+
+    let replays: Vec<_> = (0..2).map(|_| {
+        let strategy = strategy::by_name("mcts", 7, &Weights::builtin()).unwrap();
+        let bot = BotState { replaying: true, ..BotState::with_strategy(strategy) };
+        bot.strategy.borrow_mut().fix_work();
+        replay(&entries, &bot)
+    }).collect();
+    assert_eq!(replays[0], replays[1]);
+    assert_eq!(replays[0].last().unwrap().actual, Some("left".to_string()));
+}